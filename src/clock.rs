@@ -0,0 +1,148 @@
+// Copyright 2022 Steven Bosnick
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE-2.0 or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms
+
+//! Types to support changing the clock rate of the `SPI` interface at any
+//! time.
+
+use core::cmp;
+
+/// A set of clock rates that an `SPI` interface can be set to at any time.
+///
+/// The clock rate is stepped down through these rates when reads or writes
+/// keep failing with CRC errors (see
+/// [`SDCardConfig::clock_rates`](crate::SDCardConfig::clock_rates)).
+pub trait SpiClock<SPI> {
+    /// The clock rates in Hz, in any order.
+    fn rates(&self) -> &[u32];
+
+    /// Set the clock rate of `spi` to `hz`, which is one of the rates.
+    fn set_clock_rate(&mut self, spi: &mut SPI, hz: u32);
+}
+
+/// The clock type of an [`SDCard`](crate::SDCard) whose clock rate is not
+/// changed after its initilization.
+#[derive(Debug)]
+pub enum NoSpiClock {}
+
+impl<SPI> SpiClock<SPI> for NoSpiClock {
+    fn rates(&self) -> &[u32] {
+        match *self {}
+    }
+
+    fn set_clock_rate(&mut self, _: &mut SPI, _: u32) {
+        match *self {}
+    }
+}
+
+/// A list of clock rates together with the function that sets them.
+///
+/// This is created by
+/// [`SDCardConfig::clock_rates`](crate::SDCardConfig::clock_rates).
+pub struct ClockRates<F, R> {
+    set_clock_rate: F,
+    rates: R,
+}
+
+impl<F, R> ClockRates<F, R> {
+    pub(crate) fn new(rates: R, set_clock_rate: F) -> Self {
+        ClockRates {
+            set_clock_rate,
+            rates,
+        }
+    }
+}
+
+impl<SPI, F, R> SpiClock<SPI> for ClockRates<F, R>
+where
+    F: FnMut(&mut SPI, u32),
+    R: AsRef<[u32]>,
+{
+    fn rates(&self) -> &[u32] {
+        self.rates.as_ref()
+    }
+
+    fn set_clock_rate(&mut self, spi: &mut SPI, hz: u32) {
+        (self.set_clock_rate)(spi, hz)
+    }
+}
+
+/// The runtime control of the clock rate for an [`SDCard`](crate::SDCard).
+pub(crate) struct ClockControl<CLOCK> {
+    clock: CLOCK,
+    max: u32,
+    current: Option<u32>,
+}
+
+impl<CLOCK> ClockControl<CLOCK> {
+    /// Control the clock rate with `clock` up to the maximum clock rate of
+    /// the card, `max`.
+    pub fn new(clock: CLOCK, max: u32) -> Self {
+        ClockControl {
+            clock,
+            max,
+            current: None,
+        }
+    }
+
+    /// The clock rate that was last set, if any.
+    pub fn current(&self) -> Option<u32> {
+        self.current
+    }
+
+    /// Set the fastest of the configured clock rates that is no faster than
+    /// `hz` or than the maximum clock rate of the card.
+    ///
+    /// If none of the configured clock rates is slow enough then the clock
+    /// rate is left unchanged.
+    pub fn set<SPI>(&mut self, spi: &mut SPI, hz: u32)
+    where
+        CLOCK: SpiClock<SPI>,
+    {
+        let limit = cmp::min(hz, self.max);
+        let rate = self
+            .clock
+            .rates()
+            .iter()
+            .copied()
+            .filter(|&rate| rate <= limit)
+            .max();
+
+        if let Some(rate) = rate {
+            self.clock.set_clock_rate(spi, rate);
+            self.current = Some(rate);
+        }
+    }
+
+    /// Step down to the next slower configured clock rate, returning `false`
+    /// if there isn't one.
+    pub fn step_down<SPI>(&mut self, spi: &mut SPI) -> bool
+    where
+        CLOCK: SpiClock<SPI>,
+    {
+        let current = match self.current {
+            Some(current) => current,
+            None => return false,
+        };
+        let rate = self
+            .clock
+            .rates()
+            .iter()
+            .copied()
+            .filter(|&rate| rate < current)
+            .max();
+
+        match rate {
+            Some(rate) => {
+                self.clock.set_clock_rate(spi, rate);
+                self.current = Some(rate);
+                true
+            }
+            None => false,
+        }
+    }
+}
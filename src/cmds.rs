@@ -8,21 +8,52 @@
 
 //! SD Card commands and app commands
 
-use crc::{Crc, CRC_7_MMC};
-
-use crate::common::VOLTAGE_2_7_TO_3_6;
+use crate::common::{crc7, VOLTAGE_2_7_TO_3_6};
 
 /// Encode a GoIdleState command
 pub fn go_idle_state(buffer: &mut [u8]) {
     Cmd::GoIdleState.encode(0, buffer)
 }
 
+/// Encode a SwitchFunc command that selects `function` in function group 1
+/// (the access mode) and leaves the other function groups unchanged.
+pub fn switch_func(mode: SwitchFuncMode, function: u8, buffer: &mut [u8]) {
+    const UNCHANGED_GROUPS: u32 = 0x00ff_fff0;
+    Cmd::SwitchFunc.encode(
+        mode.to_arg() | UNCHANGED_GROUPS | u32::from(function & 0xf),
+        buffer,
+    )
+}
+
+/// The mode of a SwitchFunc command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwitchFuncMode {
+    /// Check which functions the card supports without switching.
+    Check,
+
+    /// Switch to the selected functions.
+    Switch,
+}
+
 /// Encode a SendIfCond command assuming  2.7-3.6 V as the voltage supplied.
 pub fn send_if_cond(check_pattern: u8, buffer: &mut [u8]) {
     let vhs: u32 = VOLTAGE_2_7_TO_3_6.into();
     Cmd::SendIfCond.encode((vhs << 8) | (check_pattern as u32), buffer)
 }
 
+/// Encode a SendOpCond command (used to initialize an MMC card).
+pub fn send_op_cond(buffer: &mut [u8]) {
+    Cmd::SendOpCond.encode(0, buffer)
+}
+
+/// Encode an IoSendOpCond command (used to detect an SDIO card).
+///
+/// The argument is 0 so that the card only reports its I/O OCR without
+/// starting the initilization of its I/O portion.
+pub fn io_send_op_cond(buffer: &mut [u8]) {
+    Cmd::IoSendOpCond.encode(0, buffer)
+}
+
 /// Encode an AppCmd command. The next command should be an application command.
 pub fn app_cmd(buffer: &mut [u8]) {
     Cmd::AppCmd.encode(0, buffer);
@@ -34,7 +65,11 @@ pub fn sd_send_op_cond(hcs: HostCapacitySupport, buffer: &mut [u8]) {
 }
 
 /// Host support for differend SD Card capacities.
-#[allow(dead_code)]
+///
+/// This is sent to the card in the SdSendOpCond app command (see section
+/// 4.2.3.1 of the Simplified Specification). An SDHC or SDXC card does not
+/// finish its initilization for an SDSC only host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum HostCapacitySupport {
     /// SDSC only host.
     ScOnly,
@@ -48,9 +83,16 @@ pub fn crc_on_off(option: CrcOption, buffer: &mut [u8]) {
     Cmd::CRCOnOff.encode(option.to_arg(), buffer)
 }
 
-#[allow(dead_code)]
+/// Whether the card checks the CRC of the commands and data that it
+/// receives.
+///
+/// This is based on section 7.2.2 of the Simplified Specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CrcOption {
+    /// The card checks the CRC.
     On,
+
+    /// The card ignores the CRC.
     Off,
 }
 
@@ -59,24 +101,234 @@ pub fn read_ocr(buffer: &mut [u8]) {
     Cmd::ReadOCR.encode(0, buffer)
 }
 
-static CRC7: Crc<u8> = Crc::<u8>::new(&CRC_7_MMC);
+/// Encode a SendCSD command.
+pub fn send_csd(buffer: &mut [u8]) {
+    Cmd::SendCSD.encode(0, buffer)
+}
+
+/// Encode a SendCID command.
+pub fn send_cid(buffer: &mut [u8]) {
+    Cmd::SendCID.encode(0, buffer)
+}
+
+/// Encode a SendSCR app command.
+pub fn send_scr(buffer: &mut [u8]) {
+    AppCmd::SendSCR.encode(0, buffer)
+}
+
+/// Encode an SdStatus app command.
+pub fn sd_status(buffer: &mut [u8]) {
+    AppCmd::SdStatus.encode(0, buffer)
+}
+
+/// Encode a SendStatus command.
+pub fn send_status(buffer: &mut [u8]) {
+    Cmd::SendStatus.encode(0, buffer)
+}
+
+/// Encode a ReadSingleBlock command for the data address `address`.
+pub fn read_single_block(address: u32, buffer: &mut [u8]) {
+    Cmd::ReadSingleBlock.encode(address, buffer)
+}
+
+/// Encode a WriteBlock command for the data address `address`.
+pub fn write_block(address: u32, buffer: &mut [u8]) {
+    Cmd::WriteBlock.encode(address, buffer)
+}
+
+/// Encode a ReadMultipleBlock command for the data address `address`.
+pub fn read_multiple_block(address: u32, buffer: &mut [u8]) {
+    Cmd::ReadMultipleBlock.encode(address, buffer)
+}
+
+/// Encode a StopTransmission command.
+pub fn stop_transmission(buffer: &mut [u8]) {
+    Cmd::StopTransmisson.encode(0, buffer)
+}
+
+/// Encode a WriteMultipleBlock command for the data address `address`.
+pub fn write_multiple_block(address: u32, buffer: &mut [u8]) {
+    Cmd::WriteMultipleBlock.encode(address, buffer)
+}
+
+/// Encode a SendNumWrBlocks app command.
+pub fn send_num_wr_blocks(buffer: &mut [u8]) {
+    AppCmd::SendNumWrBlocks.encode(0, buffer)
+}
+
+/// Encode a SetWrBlkEraseCount app command for `count` blocks.
+pub fn set_wr_blk_erase_count(count: u32, buffer: &mut [u8]) {
+    const COUNT_MASK: u32 = 0x007f_ffff;
+    AppCmd::SetWrBlkEraseCount.encode(count & COUNT_MASK, buffer)
+}
+
+/// Encode an EraseWrBlkStartAddr command for the data address `address`.
+pub fn erase_wr_blk_start_addr(address: u32, buffer: &mut [u8]) {
+    Cmd::EraseWrBlkStartAddr.encode(address, buffer)
+}
+
+/// Encode an EraseWrBlkEndAddr command for the data address `address`.
+pub fn erase_wr_blk_end_addr(address: u32, buffer: &mut [u8]) {
+    Cmd::EraseWrBlkEndAddr.encode(address, buffer)
+}
+
+/// Encode an EraseGroupStart command for the data address `address`.
+///
+/// This is the MMC command that takes the place of EraseWrBlkStartAddr.
+pub fn erase_group_start(address: u32, buffer: &mut [u8]) {
+    Cmd::EraseGroupStart.encode(address, buffer)
+}
+
+/// Encode an EraseGroupEnd command for the data address `address`.
+///
+/// This is the MMC command that takes the place of EraseWrBlkEndAddr.
+pub fn erase_group_end(address: u32, buffer: &mut [u8]) {
+    Cmd::EraseGroupEnd.encode(address, buffer)
+}
+
+/// Encode an Erase command for the erase function `kind`.
+pub fn erase(kind: EraseKind, buffer: &mut [u8]) {
+    Cmd::Erase.encode(kind.to_arg(), buffer)
+}
+
+/// The kind of erase to perform on a range of blocks.
+///
+/// This is based on section 4.3.5 of the Simplified Specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EraseKind {
+    /// Erase the blocks. The erased blocks read as either all 0s or all 1s.
+    Erase,
+
+    /// Discard the blocks. The card may deallocate the blocks without
+    /// erasing them, so the discarded blocks read as either their old
+    /// contents, all 0s or all 1s. This is faster than an erase.
+    Discard,
+
+    /// Full user area logical erase (FULE). The card physically erases the
+    /// blocks, which is useful to securely wipe the card.
+    Fule,
+}
+
+/// Encode a ProgramCSD command.
+pub fn program_csd(buffer: &mut [u8]) {
+    Cmd::ProgramCSD.encode(0, buffer)
+}
+
+/// Encode a SetBlockLen command for blocks of `length` bytes.
+pub fn set_block_len(length: u32, buffer: &mut [u8]) {
+    Cmd::SetBlockLen.encode(length, buffer)
+}
+
+/// Encode a LockUnlock command.
+pub fn lock_unlock(buffer: &mut [u8]) {
+    Cmd::LockUnlock.encode(0, buffer)
+}
+
+/// The maximum length in bytes of a card password.
+pub const MAX_PASSWORD_LEN: usize = 16;
+
+/// The maximum length in bytes of the data block for a LockUnlock command.
+pub const MAX_LOCK_UNLOCK_DATA_LEN: usize = 2 + 2 * MAX_PASSWORD_LEN;
+
+/// The operation for a LockUnlock command along with its passwords.
+///
+/// This is based on section 4.3.7 of the Simplified Specification.
+pub enum LockUnlockOp<'a> {
+    /// Set a new password, replacing the `old` password (if any).
+    SetPassword { old: &'a [u8], new: &'a [u8] },
+
+    /// Clear the password.
+    ClearPassword(&'a [u8]),
+
+    /// Lock the card with its password.
+    Lock(&'a [u8]),
+
+    /// Unlock the card with its password.
+    Unlock(&'a [u8]),
+
+    /// Erase the whole card along with its password.
+    ForceErase,
+}
+
+impl LockUnlockOp<'_> {
+    const ERASE: u8 = 0b0000_1000;
+    const LOCK_UNLOCK: u8 = 0b0000_0100;
+    const CLR_PWD: u8 = 0b0000_0010;
+    const SET_PWD: u8 = 0b0000_0001;
+
+    /// The passwords for the operation in the order in which they are sent.
+    pub fn passwords(&self) -> (&[u8], &[u8]) {
+        match *self {
+            LockUnlockOp::SetPassword { old, new } => (old, new),
+            LockUnlockOp::ClearPassword(password)
+            | LockUnlockOp::Lock(password)
+            | LockUnlockOp::Unlock(password) => (password, &[]),
+            LockUnlockOp::ForceErase => (&[], &[]),
+        }
+    }
+
+    /// Encode the data block for the operation into `buffer` and return the
+    /// length of the data block.
+    ///
+    /// Each of the passwords must be at most [`MAX_PASSWORD_LEN`] bytes long.
+    pub fn encode_data(&self, buffer: &mut [u8; MAX_LOCK_UNLOCK_DATA_LEN]) -> usize {
+        let flags = match self {
+            LockUnlockOp::SetPassword { .. } => Self::SET_PWD,
+            LockUnlockOp::ClearPassword(_) => Self::CLR_PWD,
+            LockUnlockOp::Lock(_) => Self::LOCK_UNLOCK,
+            LockUnlockOp::Unlock(_) => 0,
+            LockUnlockOp::ForceErase => return Self::encode_force_erase(buffer),
+        };
+        let (first, second) = self.passwords();
+        let len = first.len() + second.len();
+
+        buffer[0] = flags;
+        buffer[1] = len as u8;
+        buffer[2..2 + first.len()].copy_from_slice(first);
+        buffer[2 + first.len()..2 + len].copy_from_slice(second);
+
+        2 + len
+    }
+
+    fn encode_force_erase(buffer: &mut [u8; MAX_LOCK_UNLOCK_DATA_LEN]) -> usize {
+        buffer[0] = Self::ERASE;
+
+        1
+    }
+}
+
+/// Encode a SetWriteProt command for the data address `address`.
+pub fn set_write_prot(address: u32, buffer: &mut [u8]) {
+    Cmd::SetWriteProt.encode(address, buffer)
+}
+
+/// Encode a ClrWriteProt command for the data address `address`.
+pub fn clr_write_prot(address: u32, buffer: &mut [u8]) {
+    Cmd::ClrWriteProt.encode(address, buffer)
+}
+
+/// Encode a SendWriteProt command for the data address `address`.
+pub fn send_write_prot(address: u32, buffer: &mut [u8]) {
+    Cmd::SendWriteProt.encode(address, buffer)
+}
 
 // This enum has all of the allowed commands for an SD Card in SPI mode,
 // including ones that this package does not use. This is taken from Table 7-3
 // of the Simplifed Specification.
-#[allow(dead_code)]
+#[allow(dead_code, clippy::enum_variant_names)]
 #[repr(u8)]
 #[derive(Clone, Copy)]
 enum Cmd {
     GoIdleState = 0,
     SendOpCond = 1,
+    IoSendOpCond = 5,
     SwitchFunc = 6,
     SendIfCond = 8,
     SendCSD = 9,
     SendCID = 10,
     StopTransmisson = 12,
     SendStatus = 13,
-    SendBlockLen = 16,
+    SetBlockLen = 16,
     ReadSingleBlock = 17,
     ReadMultipleBlock = 18,
     WriteBlock = 24,
@@ -87,6 +339,8 @@ enum Cmd {
     SendWriteProt = 30,
     EraseWrBlkStartAddr = 32,
     EraseWrBlkEndAddr = 33,
+    EraseGroupStart = 35,
+    EraseGroupEnd = 36,
     Erase = 38,
     LockUnlock = 42,
     AppCmd = 55,
@@ -138,11 +392,11 @@ trait Encode: Copy {
 }
 
 fn encode_end_byte(bytes: &[u8]) -> u8 {
-    (CRC7.checksum(bytes) << 1) | CMD_END
+    (crc7(bytes) << 1) | CMD_END
 }
 
 impl HostCapacitySupport {
-    fn to_arg(&self) -> u32 {
+    fn to_arg(self) -> u32 {
         const HCR_BIT: u32 = 0b0100_0000_0000_0000_0000_0000_0000_0000;
         match self {
             HostCapacitySupport::ScOnly => 0,
@@ -151,8 +405,27 @@ impl HostCapacitySupport {
     }
 }
 
+impl EraseKind {
+    fn to_arg(self) -> u32 {
+        match self {
+            EraseKind::Erase => 0x0000_0000,
+            EraseKind::Discard => 0x0000_0001,
+            EraseKind::Fule => 0x0000_0002,
+        }
+    }
+}
+
+impl SwitchFuncMode {
+    fn to_arg(self) -> u32 {
+        match self {
+            SwitchFuncMode::Check => 0x0000_0000,
+            SwitchFuncMode::Switch => 0x8000_0000,
+        }
+    }
+}
+
 impl CrcOption {
-    fn to_arg(&self) -> u32 {
+    fn to_arg(self) -> u32 {
         match self {
             CrcOption::On => 0x0000_0001,
             CrcOption::Off => 0x0000_0000,
@@ -203,7 +476,7 @@ mod tests {
         Cmd::ReadSingleBlock.encode(addr, &mut buffer);
 
         assert_eq!(&buffer[0..5], [0x51, 0x12, 0x34, 0x56, 0x78]);
-        assert_eq!((buffer[5] & 0b1111_1110) >> 1, CRC7.checksum(&buffer[0..5]));
+        assert_eq!((buffer[5] & 0b1111_1110) >> 1, crc7(&buffer[0..5]));
     }
 
     #[test]
@@ -213,7 +486,7 @@ mod tests {
         AppCmd::SdStatus.encode(0, &mut buffer);
 
         assert_eq!(&buffer[0..5], [0x4d, 0x00, 0x00, 0x00, 0x00]);
-        assert_eq!((buffer[5] & 0b1111_1110) >> 1, CRC7.checksum(&buffer[0..5]));
+        assert_eq!((buffer[5] & 0b1111_1110) >> 1, crc7(&buffer[0..5]));
     }
 
     #[test]
@@ -223,7 +496,69 @@ mod tests {
         AppCmd::SetWrBlkEraseCount.encode(0x01, &mut buffer);
 
         assert_eq!(&buffer[0..5], [0x57, 0x00, 0x00, 0x00, 0x01]);
-        assert_eq!((buffer[5] & 0b1111_1110) >> 1, CRC7.checksum(&buffer[0..5]));
+        assert_eq!((buffer[5] & 0b1111_1110) >> 1, crc7(&buffer[0..5]));
+    }
+
+    #[test]
+    fn erase_wr_blk_start_addr_encodes_as_expected() {
+        let mut buffer = [0; 6];
+
+        erase_wr_blk_start_addr(0x1234_5678, &mut buffer);
+
+        assert_eq!(&buffer[0..5], [0x60, 0x12, 0x34, 0x56, 0x78]);
+        assert_eq!((buffer[5] & 0b1111_1110) >> 1, crc7(&buffer[0..5]));
+    }
+
+    #[test]
+    fn erase_group_start_encodes_as_expected() {
+        let mut buffer = [0; 6];
+
+        erase_group_start(0x1234_5678, &mut buffer);
+
+        assert_eq!(&buffer[0..5], [0x63, 0x12, 0x34, 0x56, 0x78]);
+        assert_eq!((buffer[5] & 0b1111_1110) >> 1, crc7(&buffer[0..5]));
+    }
+
+    #[test]
+    fn erase_with_discard_encodes_as_expected() {
+        let mut buffer = [0; 6];
+
+        erase(EraseKind::Discard, &mut buffer);
+
+        assert_eq!(&buffer[0..5], [0x66, 0x00, 0x00, 0x00, 0x01]);
+        assert_eq!((buffer[5] & 0b1111_1110) >> 1, crc7(&buffer[0..5]));
+    }
+
+    #[test]
+    fn lock_unlock_set_password_data_has_both_passwords() {
+        let mut buffer = [0; MAX_LOCK_UNLOCK_DATA_LEN];
+        let op = LockUnlockOp::SetPassword {
+            old: b"ab",
+            new: b"xyz",
+        };
+
+        let len = op.encode_data(&mut buffer);
+
+        assert_eq!(&buffer[..len], [0x01, 5, b'a', b'b', b'x', b'y', b'z']);
+    }
+
+    #[test]
+    fn lock_unlock_force_erase_data_is_one_byte() {
+        let mut buffer = [0; MAX_LOCK_UNLOCK_DATA_LEN];
+
+        let len = LockUnlockOp::ForceErase.encode_data(&mut buffer);
+
+        assert_eq!(&buffer[..len], [0x08]);
+    }
+
+    #[test]
+    fn switch_func_encodes_as_expected() {
+        let mut buffer = [0; 6];
+
+        switch_func(SwitchFuncMode::Switch, 1, &mut buffer);
+
+        assert_eq!(&buffer[0..5], [0x46, 0x80, 0xff, 0xff, 0xf1]);
+        assert_eq!((buffer[5] & 0b1111_1110) >> 1, crc7(&buffer[0..5]));
     }
 
     #[test]
@@ -234,7 +569,7 @@ mod tests {
         send_if_cond(check_pattern, &mut buffer);
 
         assert_eq!(&buffer[0..5], [0x48, 0x00, 0x00, 0x01, check_pattern]);
-        assert_eq!((buffer[5] & 0b1111_1110) >> 1, CRC7.checksum(&buffer[0..5]));
+        assert_eq!((buffer[5] & 0b1111_1110) >> 1, crc7(&buffer[0..5]));
     }
 
     #[test]
@@ -244,6 +579,6 @@ mod tests {
         sd_send_op_cond(HostCapacitySupport::HcOrXcSupported, &mut buffer);
 
         assert_eq!(&buffer[0..5], [0x69, 0x40, 0x00, 0x00, 0x00]);
-        assert_eq!((buffer[5] & 0b1111_1110) >> 1, CRC7.checksum(&buffer[0..5]));
+        assert_eq!((buffer[5] & 0b1111_1110) >> 1, crc7(&buffer[0..5]));
     }
 }
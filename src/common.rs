@@ -9,6 +9,8 @@
 //! Constants from the Simplified Specificiation that are used in more than
 //! one module.
 
+use crc::{Crc, CRC_7_MMC};
+
 /// Voltage supplied or accepted nibble.
 ///
 /// This is used as the voltage supplied value (VHS) for a SendIfCond command
@@ -25,11 +27,70 @@ pub const VOLTAGE_2_7_TO_3_6: u8 = 0b0001;
 /// This could be any value but this the one we picked.
 pub const IF_COND_CHECK_PATTERN: u8 = 0b0101_0101;
 
+/// The size of a data block in bytes.
+///
+/// SDHC and SDXC cards have a fixed block length of 512 bytes and SDSC cards
+/// use this as their default block length (see section 7.2.3).
+pub const BLOCK_SIZE: usize = 512;
+
+/// The maximum SPI clock rate in Hz for a card in Default Speed mode (see
+/// section 7.1).
+pub const DEFAULT_SPEED_CLOCK: u32 = 25_000_000;
+
+/// The maximum SPI clock rate in Hz for a card in High Speed mode (see
+/// section 4.3.10).
+pub const HIGH_SPEED_CLOCK: u32 = 50_000_000;
+
+static CRC7: Crc<u8> = Crc::<u8>::new(&CRC_7_MMC);
+
+/// Calculate the 7 bit CRC used for commands and for the CID and CSD
+/// registers (see section 4.5).
+pub fn crc7(bytes: &[u8]) -> u8 {
+    CRC7.checksum(bytes)
+}
+
+/// The type of memory card.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardType {
+    /// SD Memory Card
+    Sd,
+
+    /// MultiMediaCard
+    ///
+    /// These are legacy cards that do not accept the SdSendOpCond app
+    /// command and are initialized with the SendOpCond command instead.
+    Mmc,
+}
+
+/// The version of the physical layer specification that a card complies
+/// with, as far as the initilization can tell them apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    /// Version 1.x (the card rejects the SendIfCond command)
+    V1,
+
+    /// Version 2.00 or later (the card accepts the SendIfCond command)
+    V2,
+}
+
+/// A type of card that cannot be used as a memory card.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnsupportedCardType {
+    /// SDIO card without a memory portion
+    Sdio,
+
+    /// High capacity MMC card in sector addressing mode
+    ///
+    /// The size of these cards is only reported in their EXT_CSD register,
+    /// which is not read.
+    SectorModeMmc,
+}
+
 /// The card capacity classification from section 3.3.2.
 ///
 /// Note that Ultra Capacity (SDUC) cards are not supported in SPI mode
 /// (see section 7.1) so there is no entry for them here.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum CardCapacity {
     /// SDSC card
     Standard,
@@ -37,3 +98,29 @@ pub enum CardCapacity {
     /// SDHC or SDXC card
     HighOrExtended,
 }
+
+impl CardCapacity {
+    /// The data address argument for the data block with index `block`.
+    ///
+    /// SDSC cards use byte addressing while SDHC and SDXC cards use block
+    /// addressing (see section 7.2.3). This is `None` if the byte address of
+    /// the data block does not fit in the argument of a command.
+    pub fn data_address(self, block: u32) -> Option<u32> {
+        match self {
+            CardCapacity::Standard => block.checked_mul(BLOCK_SIZE as u32),
+            CardCapacity::HighOrExtended => Some(block),
+        }
+    }
+
+    /// The time (in microseconds) that the card is allowed to be busy after
+    /// each data block that is written to it.
+    ///
+    /// This is 250 ms for SDSC cards and 500 ms for SDHC and SDXC cards (see
+    /// section 4.6.2.2).
+    pub fn write_timeout(self) -> u32 {
+        match self {
+            CardCapacity::Standard => 250_000,
+            CardCapacity::HighOrExtended => 500_000,
+        }
+    }
+}
@@ -0,0 +1,210 @@
+// Copyright 2022 Steven Bosnick
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE-2.0 or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms
+
+//! The configuration for the initilization of an SD Card.
+
+use core::{
+    fmt::{self, Debug},
+    marker::PhantomData,
+};
+
+use crate::{
+    clock::{ClockRates, NoSpiClock},
+    cmds::{CrcOption, HostCapacitySupport},
+    timer::{NoTimer, Timer},
+    transactions::InitConfig,
+};
+
+/// A function that increases the clock rate of an `SPI` interface to at
+/// most the given rate in Hz (see [`SDCardConfig::speed_increase`]).
+///
+/// This is the type of the speed increase of an [`SDCardConfig`] that does
+/// not have one.
+pub type IncreaseSpeed<SPI> = fn(SPI, u32) -> SPI;
+
+/// A builder for the configuration of the initilization of an
+/// [`SDCard`](crate::SDCard).
+///
+/// The configuration is passed to
+/// [`SDCard::with_config`](crate::SDCard::with_config). It starts out with
+/// the same settings that [`SDCard::new`](crate::SDCard::new) uses, and each
+/// of the builder methods changes one of the settings. The ready and
+/// response timeouts, the timer and the clock rates apply to every command
+/// that is sent to the card, while the other settings only apply to its
+/// initilization.
+pub struct SDCardConfig<SPI, TIMER = NoTimer, INCREASE = IncreaseSpeed<SPI>, CLOCK = NoSpiClock> {
+    pub(crate) init: InitConfig,
+    pub(crate) increase_speed: Option<INCREASE>,
+    pub(crate) timer: Option<TIMER>,
+    pub(crate) clock: Option<CLOCK>,
+    spi: PhantomData<fn(SPI) -> SPI>,
+}
+
+impl<SPI> SDCardConfig<SPI> {
+    /// Create a configuration with the default settings.
+    pub fn new() -> Self {
+        SDCardConfig {
+            init: InitConfig::default(),
+            increase_speed: None,
+            timer: None,
+            clock: None,
+            spi: PhantomData,
+        }
+    }
+}
+
+impl<SPI, TIMER, INCREASE, CLOCK> SDCardConfig<SPI, TIMER, INCREASE, CLOCK> {
+    /// Set how long (in microseconds) to wait for the card to be ready for a
+    /// command and the delay (in microseconds) between the checks.
+    ///
+    /// The default timeout is 3 ms with a delay of 10 µs.
+    pub fn ready_timeout(mut self, timeout_us: u32, delay_us: u16) -> Self {
+        self.init.waits.ready_timeout = timeout_us;
+        self.init.waits.ready_delay = delay_us;
+        self
+    }
+
+    /// Set how long (in microseconds) to wait for the card to respond to a
+    /// command.
+    ///
+    /// The default timeout is the time to receive 8 bytes (N_CR from section
+    /// 7.5.4 of the Simplified Specification) at 100 kHz. Without a timer
+    /// each byte that is received while waiting is counted as the 80 µs that
+    /// it takes at 100 kHz.
+    pub fn response_timeout(mut self, timeout_us: u32) -> Self {
+        self.init.waits.response_timeout = timeout_us;
+        self
+    }
+
+    /// Set the number of times to send the SendIfCond command while the
+    /// card responds with the wrong check pattern.
+    pub fn if_cond_retries(mut self, count: u32) -> Self {
+        self.init.if_cond_count = count;
+        self
+    }
+
+    /// Set how long (in microseconds) to keep sending the SdSendOpCond app
+    /// command while the card is still initializing and the delay (in
+    /// microseconds) between them.
+    ///
+    /// The default timeout is the 1 s from section 4.2.3 of the Simplified
+    /// Specification.
+    pub fn op_cond_timeout(mut self, timeout_us: u32, delay_us: u16) -> Self {
+        self.init.op_cond_timeout = timeout_us;
+        self.init.op_cond_delay = delay_us;
+        self
+    }
+
+    /// Set whether the card checks the CRC of the commands and data that it
+    /// receives.
+    pub fn crc(mut self, crc: CrcOption) -> Self {
+        self.init.crc = crc;
+        self
+    }
+
+    /// Set the card capacities that the host supports.
+    pub fn host_capacity_support(mut self, support: HostCapacitySupport) -> Self {
+        self.init.host_capacity_support = support;
+        self
+    }
+
+    /// Set the supply voltage (in mV) that the host provides to the card.
+    ///
+    /// The initilization fails if the supply voltage is outside of the
+    /// voltage window of the card (see
+    /// [`InitilizationError::card_voltage_window`](crate::InitilizationError::card_voltage_window)).
+    pub fn host_voltage(mut self, millivolts: u16) -> Self {
+        self.init.host_voltage = millivolts;
+        self
+    }
+
+    /// Set the function that increases the clock rate of the `SPI` interface
+    /// after the card is initialized.
+    ///
+    /// The function is passed the maximum clock rate in Hz that the card
+    /// supports, which is decoded from TRAN_SPEED in the CSD register of the
+    /// card. This is usually 50 MHz if the card was switched to High Speed
+    /// mode or 25 MHz (the maximum speed for an SD card in Default Speed mode)
+    /// otherwise. The function should set the fastest clock rate that the
+    /// `SPI` interface can provide without exceeding the maximum.
+    pub fn speed_increase<F>(self, increase_speed: F) -> SDCardConfig<SPI, TIMER, F, CLOCK>
+    where
+        F: FnOnce(SPI, u32) -> SPI,
+    {
+        SDCardConfig {
+            init: self.init,
+            increase_speed: Some(increase_speed),
+            timer: self.timer,
+            clock: self.clock,
+            spi: PhantomData,
+        }
+    }
+
+    /// Set the monotonic timer that measures the timeouts while waiting for
+    /// the card.
+    ///
+    /// Without a timer the timeouts are estimated from the delays between
+    /// the checks of the card, so they take longer than the Simplified
+    /// Specification allows on a slow `SPI` interface.
+    pub fn timer<T: Timer>(self, timer: T) -> SDCardConfig<SPI, T, INCREASE, CLOCK> {
+        SDCardConfig {
+            init: self.init,
+            increase_speed: self.increase_speed,
+            timer: Some(timer),
+            clock: self.clock,
+            spi: PhantomData,
+        }
+    }
+
+    /// Set the clock rates (in Hz) that the `SPI` interface can be set to at
+    /// any time and the function that sets them.
+    ///
+    /// After the card is initialized (and after any speed increase) the
+    /// fastest of `rates` that the card supports is set. If reads or writes
+    /// then keep failing with CRC errors the clock rate is stepped down to the
+    /// next slower rate and the read or write is retried. The rate can also
+    /// be changed with [`SDCard::set_clock_rate`](crate::SDCard::set_clock_rate).
+    ///
+    /// `rates` can be any list of rates, such as an array or a slice, and
+    /// `set_clock_rate` can capture state, such as the handle of the clock
+    /// peripheral of the `SPI` interface.
+    pub fn clock_rates<F, R>(
+        self,
+        rates: R,
+        set_clock_rate: F,
+    ) -> SDCardConfig<SPI, TIMER, INCREASE, ClockRates<F, R>>
+    where
+        F: FnMut(&mut SPI, u32),
+        R: AsRef<[u32]>,
+    {
+        SDCardConfig {
+            init: self.init,
+            increase_speed: self.increase_speed,
+            timer: self.timer,
+            clock: Some(ClockRates::new(rates, set_clock_rate)),
+            spi: PhantomData,
+        }
+    }
+}
+
+impl<SPI> Default for SDCardConfig<SPI> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<SPI, TIMER, INCREASE, CLOCK> Debug for SDCardConfig<SPI, TIMER, INCREASE, CLOCK> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SDCardConfig")
+            .field("init", &self.init)
+            .field("increase_speed", &self.increase_speed.is_some())
+            .field("timer", &self.timer.is_some())
+            .field("clock", &self.clock.is_some())
+            .finish()
+    }
+}
@@ -12,17 +12,26 @@
 #![forbid(unsafe_code)]
 #![deny(missing_docs, warnings)]
 
+mod clock;
 mod cmds;
 mod common;
+mod config;
 mod resp;
+mod timer;
+mod tokens;
 mod transactions;
 
 #[cfg(test)]
 mod testutils;
 
-use core::fmt::Debug;
+use core::{cmp, fmt::Debug};
 
-use common::CardCapacity;
+use clock::ClockControl;
+pub use clock::{ClockRates, NoSpiClock, SpiClock};
+use cmds::LockUnlockOp;
+pub use cmds::{CrcOption, EraseKind, HostCapacitySupport};
+pub use common::{CardType, UnsupportedCardType, Version};
+pub use config::{IncreaseSpeed, SDCardConfig};
 use embedded_hal::{
     blocking::{
         delay::DelayUs,
@@ -31,24 +40,78 @@ use embedded_hal::{
     digital::v2::OutputPin,
 };
 use embedded_storage::{ReadStorage, Storage};
+use resp::Csd;
+pub use resp::{
+    CapacityClass, CardStatusError, Cid, ProgrammableCsd, Scr, SdStatus, Security, VoltageWindow,
+};
 use snafu::{prelude::*, IntoError};
-use transactions::{initilization_flow, power_up_card, with_cs_low};
+use timer::TimedDelay;
+pub use timer::{NoTimer, Timer};
+use transactions::{
+    clr_write_prot, erase, initilization_flow, is_locked, lock_unlock, power_up_card, program_csd,
+    read, read_blocks, read_cid, read_csd, read_scr, read_sd_status, send_status, send_write_prot,
+    set_write_prot, switch_to_high_speed, with_cs_low, write, write_blocks, CardProperties,
+    CommandWaits, InitConfig,
+};
+
+/// The size in bytes of a [`Block`].
+pub const BLOCK_SIZE: usize = common::BLOCK_SIZE;
+
+/// A block of data on the card.
+///
+/// The blocks on the card are addressed by their index (their logical block
+/// address) by [`SDCard::read_blocks`] and [`SDCard::write_blocks`],
+/// independent of whether the card uses byte or block addressing internally.
+pub type Block = [u8; BLOCK_SIZE];
 
 /// An SD Card interface built from an SPI periferal and a Chip Select pin.
 ///
 /// We need the Chip Select to be separate so we can write some bytes without
-/// Chip Select asserted to put the card into SPI mode.
-pub struct SDCard<SPI, CS, DELAY> {
+/// Chip Select asserted to put the card into SPI mode. The timeouts while
+/// waiting for the card are measured with `TIMER` if the card was created
+/// with a timer (see [`SDCardConfig::timer`]), and the clock rate of the
+/// `SPI` interface is changed with `CLOCK` if the card was created with clock
+/// rates (see [`SDCardConfig::clock_rates`]).
+pub struct SDCard<SPI, CS, DELAY, TIMER = NoTimer, CLOCK = NoSpiClock> {
     spi: SPI,
     cs: CS,
-    // TODO: removed this when it is no longer needed
-    #[allow(dead_code)]
-    delay: DELAY,
-    // TODO: removed this when it is no longer needed
-    #[allow(dead_code)]
-    capacity: CardCapacity,
+    delay: TimedDelay<DELAY, TIMER>,
+    waits: CommandWaits,
+    properties: CardProperties,
+    csd: Csd,
+    cid: Cid,
+    scr: Option<Scr>,
+    sd_status: Option<SdStatus>,
+    locked: bool,
+    clock: Option<ClockControl<CLOCK>>,
+}
+
+/// A description of an initialized card.
+///
+/// This is available through [`SDCard::info`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CardInfo {
+    /// The version of the physical layer specification of the card.
+    pub version: Version,
+
+    /// The capacity class of the card.
+    pub capacity_class: CapacityClass,
+
+    /// The number of blocks on the card.
+    pub block_count: u32,
+
+    /// The voltage window from the OCR register of the card.
+    pub voltage_window: VoltageWindow,
+
+    /// Whether CRC checking is turned on for the commands and data that are
+    /// sent to the card.
+    pub crc_enabled: bool,
 }
 
+/// The number of CRC errors in a row after which the clock rate is stepped
+/// down.
+const MAX_CRC_ERRORS: u32 = 2;
+
 impl<SPI, CS, DELAY> SDCard<SPI, CS, DELAY>
 where
     SPI: Debug + Write<u8> + Transfer<u8>,
@@ -70,30 +133,131 @@ where
     /// After the SD card has been initialized, the clock rate on the `SPI`
     /// interface can be increased through the supplied `increase_speed` closure.
     /// The speed should be increased to 25 MHz (the maximum speed for an SD card
-    /// using `SPI` mode).
+    /// using `SPI` mode). See [`SDCardConfig::speed_increase`] for a closure
+    /// that is passed the maximum clock rate that the card supports instead.
     pub fn with_speed_increase(
+        spi: SPI,
+        cs: CS,
+        delay: DELAY,
+        increase_speed: impl FnOnce(SPI) -> SPI,
+    ) -> Result<Self, InitilizationError<SPI, CS>> {
+        let config = SDCardConfig::new().speed_increase(|spi, _| increase_speed(spi));
+
+        Self::with_config(spi, cs, delay, config)
+    }
+}
+
+impl<SPI, CS, DELAY, TIMER, CLOCK> SDCard<SPI, CS, DELAY, TIMER, CLOCK>
+where
+    SPI: Debug + Write<u8> + Transfer<u8>,
+    CS: Debug + OutputPin,
+    DELAY: DelayUs<u16>,
+    TIMER: Timer,
+    CLOCK: SpiClock<SPI>,
+{
+    /// Create a new [`SDCard`] using the settings from `config`.
+    ///
+    /// The `SPI` interface should have a clock rate between 100 kHz and 400 kHz.
+    /// The clock rate is only changed after the card initilization is
+    /// complete if `config` has a speed increase function or clock rates (see
+    /// [`SDCardConfig::speed_increase`] and [`SDCardConfig::clock_rates`]).
+    pub fn with_config<INCREASE>(
+        spi: SPI,
+        cs: CS,
+        delay: DELAY,
+        config: SDCardConfig<SPI, TIMER, INCREASE, CLOCK>,
+    ) -> Result<Self, InitilizationError<SPI, CS>>
+    where
+        INCREASE: FnOnce(SPI, u32) -> SPI,
+    {
+        let increase_speed = config.increase_speed;
+
+        Self::initialize(
+            spi,
+            cs,
+            delay,
+            config.timer,
+            config.clock,
+            &config.init,
+            |spi, hz| match increase_speed {
+                Some(increase_speed) => increase_speed(spi, hz),
+                None => spi,
+            },
+        )
+    }
+
+    fn initialize(
         mut spi: SPI,
         mut cs: CS,
-        mut delay: DELAY,
-        increase_speed: impl FnOnce(SPI) -> SPI,
+        delay: DELAY,
+        timer: Option<TIMER>,
+        clock: Option<CLOCK>,
+        config: &InitConfig,
+        increase_speed: impl FnOnce(SPI, u32) -> SPI,
     ) -> Result<Self, InitilizationError<SPI, CS>> {
         // This initialized the SD card using the power up sequence in section
         // 6.4.1 followed by the initilization flow from Figure 7-2. (Unless
         // otherwise indicated the section and figure refences in the comments
         // are references to the Simplifed Specification).
 
-        let result = power_up_card(&mut spi, &mut cs, &mut delay)
-            .and_then(|_| with_cs_low(&mut cs, &mut spi, &mut delay, initilization_flow));
+        let mut delay = TimedDelay { delay, timer };
+        let waits = &config.waits;
+        let result = power_up_card(&mut spi, &mut cs, &mut delay).and_then(|_| {
+            with_cs_low(&mut cs, &mut spi, &mut delay, |spi, delay| {
+                let properties = initilization_flow(spi, delay, config)?;
+                let card_type = properties.card_type;
+                let csd = read_csd(spi, delay, waits, card_type)?;
+                let cid = read_cid(spi, delay, waits)?;
+                // MMC cards have neither an SCR nor an SD Status.
+                let (scr, sd_status) = match card_type {
+                    CardType::Sd => (
+                        Some(read_scr(spi, delay, waits)?),
+                        Some(read_sd_status(spi, delay, waits)?),
+                    ),
+                    CardType::Mmc => (None, None),
+                };
+                let locked = is_locked(spi, delay, waits)?;
+                // A locked card does not accept the SwitchFunc command (see
+                // section 4.3.7.6). The card updates TRAN_SPEED in its CSD
+                // register after switching to High Speed mode.
+                let high_speed = csd.supports_switch_func()
+                    && !locked
+                    && switch_to_high_speed(spi, delay, waits)?;
+                let csd = if high_speed {
+                    read_csd(spi, delay, waits, card_type)?
+                } else {
+                    csd
+                };
+                Ok((properties, csd, cid, scr, sd_status, locked, high_speed))
+            })
+        });
 
         match result {
-            Ok(capacity) => {
+            Ok((properties, csd, cid, scr, sd_status, locked, high_speed)) => {
                 // 8. (optional) Increase frequency of the SPI
-                let spi = increase_speed(spi);
+                let max_clock = csd.max_clock().unwrap_or(if high_speed {
+                    common::HIGH_SPEED_CLOCK
+                } else {
+                    common::DEFAULT_SPEED_CLOCK
+                });
+                let mut spi = increase_speed(spi, max_clock);
+                let clock = clock.map(|clock| {
+                    let mut control = ClockControl::new(clock, max_clock);
+                    control.set(&mut spi, max_clock);
+                    control
+                });
                 Ok(Self {
                     cs,
                     spi,
-                    capacity,
+                    properties,
+                    csd,
+                    cid,
+                    scr,
+                    sd_status,
+                    locked,
+                    clock,
                     delay,
+                    waits: *waits,
                 })
             }
             Err(e) => Err(InitilizationSnafu { cs, spi }.into_error(e)),
@@ -101,10 +265,479 @@ where
     }
 }
 
-impl<SPI, CS, DELAY> SDCard<SPI, CS, DELAY> {
+impl<SPI, CS, DELAY, TIMER, CLOCK> SDCard<SPI, CS, DELAY, TIMER, CLOCK>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    CS: OutputPin,
+    DELAY: DelayUs<u16>,
+    TIMER: Timer,
+    CLOCK: SpiClock<SPI>,
+{
+    /// Read consecutive blocks from the card, starting with the block at
+    /// index `start_lba`, until `blocks` is full.
+    ///
+    /// Unlike [`ReadStorage::read`] this can reach every block on cards that
+    /// are larger than 4 GiB. Nothing is read if any of the blocks is past
+    /// the end of the card (see [`SDCard::num_blocks`]).
+    pub fn read_blocks(&mut self, start_lba: u32, blocks: &mut [Block]) -> Result<(), IOError> {
+        self.check_unlocked()?;
+        self.check_block_range(start_lba, blocks.len() as u64)?;
+        let capacity = self.properties.capacity;
+        let count = blocks.len() as u32;
+
+        self.with_clock_retry(|spi, delay, waits| {
+            let blocks = blocks.iter_mut().map(|block| &mut block[..]);
+            read_blocks(spi, delay, waits, capacity, start_lba, count, blocks)
+        })
+    }
+
+    /// Write `blocks` to consecutive blocks on the card, starting with the
+    /// block at index `start_lba`.
+    ///
+    /// Unlike [`Storage::write`] this can reach every block on cards that
+    /// are larger than 4 GiB. Nothing is written if any of the blocks is past
+    /// the end of the card (see [`SDCard::num_blocks`]).
+    pub fn write_blocks(&mut self, start_lba: u32, blocks: &[Block]) -> Result<(), IOError> {
+        self.check_unlocked()?;
+        self.check_not_write_protected()?;
+        self.check_block_range(start_lba, blocks.len() as u64)?;
+        let capacity = self.properties.capacity;
+        let card_type = self.properties.card_type;
+        let count = blocks.len() as u32;
+
+        self.with_clock_retry(|spi, delay, waits| {
+            let blocks = blocks.iter().map(|block| &block[..]);
+            write_blocks(
+                spi, delay, waits, capacity, card_type, start_lba, count, blocks,
+            )
+        })
+    }
+
+    /// Erase the blocks from index `start_block` to index `end_block`
+    /// (inclusive) using the erase function `kind`.
+    ///
+    /// The time allowed for the erase is calculated from the erase timing in
+    /// the SD Status of the card, or from the default of 250 ms for each
+    /// allocation unit when the card has no SD Status, so erasing many blocks
+    /// at once can take a long time to complete. [`EraseKind::Discard`] and
+    /// [`EraseKind::Fule`] are refused unless the card reports support for
+    /// them in its SD Status. An MMC card only erases whole erase groups, so
+    /// the range must start and end on an erase group boundary.
+    pub fn erase(
+        &mut self,
+        start_block: u32,
+        end_block: u32,
+        kind: EraseKind,
+    ) -> Result<(), IOError> {
+        self.check_unlocked()?;
+        self.check_not_write_protected()?;
+        if start_block > end_block {
+            return Err(IOSnafu {}.into_error(transactions::Error::InvalidEraseRange));
+        }
+        self.check_block_range(start_block, u64::from(end_block - start_block) + 1)?;
+        let blocks = end_block - start_block + 1;
+
+        let supported = match (kind, self.sd_status) {
+            (EraseKind::Erase, _) => true,
+            (EraseKind::Discard, Some(sd_status)) => sd_status.supports_discard(),
+            (EraseKind::Fule, Some(sd_status)) => sd_status.supports_fule(),
+            (_, None) => false,
+        };
+        if !supported {
+            return Err(IOSnafu {}.into_error(transactions::Error::UnsupportedErase { kind }));
+        }
+        if let Some(group_size) = self.csd.erase_group_size() {
+            if group_size != 0 && (start_block % group_size, blocks % group_size) != (0, 0) {
+                return Err(
+                    IOSnafu {}.into_error(transactions::Error::MisalignedErase { group_size })
+                );
+            }
+        }
+
+        let capacity = self.properties.capacity;
+        let card_type = self.properties.card_type;
+        let busy_timeout = match self.sd_status {
+            Some(sd_status) => sd_status.erase_busy_timeout(blocks),
+            None => self.csd.erase_busy_timeout(blocks),
+        };
+
+        with_cs_low(
+            &mut self.cs,
+            &mut self.spi,
+            &mut self.delay,
+            |spi, delay| {
+                erase(
+                    spi,
+                    delay,
+                    &self.waits,
+                    capacity,
+                    card_type,
+                    start_block,
+                    end_block,
+                    kind,
+                    busy_timeout,
+                )
+            },
+        )
+        .context(IOSnafu {})
+    }
+
+    /// Write protect the write protect group that contains the block at
+    /// index `block`.
+    ///
+    /// This is only supported on cards that have write protect groups (see
+    /// [`SDCard::write_protect_group_size`]). A later write to a block in the
+    /// group fails with an [`IOError`] that reports
+    /// [`CardStatusError::WpViolation`] through [`IOError::card_status`].
+    pub fn set_write_protect(&mut self, block: u32) -> Result<(), IOError> {
+        self.check_write_protect_groups()?;
+        self.check_block_range(block, 1)?;
+        let capacity = self.properties.capacity;
+
+        with_cs_low(
+            &mut self.cs,
+            &mut self.spi,
+            &mut self.delay,
+            |spi, delay| set_write_prot(spi, delay, &self.waits, capacity, block),
+        )
+        .context(IOSnafu {})
+    }
+
+    /// Clear the write protection of the write protect group that contains
+    /// the block at index `block`.
+    ///
+    /// This is only supported on cards that have write protect groups (see
+    /// [`SDCard::write_protect_group_size`]).
+    pub fn clear_write_protect(&mut self, block: u32) -> Result<(), IOError> {
+        self.check_write_protect_groups()?;
+        self.check_block_range(block, 1)?;
+        let capacity = self.properties.capacity;
+
+        with_cs_low(
+            &mut self.cs,
+            &mut self.spi,
+            &mut self.delay,
+            |spi, delay| clr_write_prot(spi, delay, &self.waits, capacity, block),
+        )
+        .context(IOSnafu {})
+    }
+
+    /// Read the write protection of the 32 write protect groups starting with
+    /// the group that contains the block at index `block`.
+    ///
+    /// Each bit is set if the corresponding group is write protected. The
+    /// least significant bit is for the group that contains `block`. This is
+    /// only supported on cards that have write protect groups (see
+    /// [`SDCard::write_protect_group_size`]).
+    pub fn write_protect_status(&mut self, block: u32) -> Result<u32, IOError> {
+        self.check_write_protect_groups()?;
+        self.check_block_range(block, 1)?;
+        let capacity = self.properties.capacity;
+
+        with_cs_low(
+            &mut self.cs,
+            &mut self.spi,
+            &mut self.delay,
+            |spi, delay| send_write_prot(spi, delay, &self.waits, capacity, block),
+        )
+        .context(IOSnafu {})
+    }
+
+    /// Replace the programmable part of the CSD register of the card with
+    /// `programmable`.
+    ///
+    /// This is used to write protect the whole card, either temporarily or
+    /// permanently. Once the card is write protected [`SDCard::write_blocks`]
+    /// and [`Storage::write`] fail without sending anything to the card. Note
+    /// that the permanent write protection and the copy flag can never be
+    /// cleared once they have been set.
+    pub fn program_csd(&mut self, programmable: ProgrammableCsd) -> Result<(), IOError> {
+        let csd = self.csd.with_programmable(programmable);
+
+        with_cs_low(
+            &mut self.cs,
+            &mut self.spi,
+            &mut self.delay,
+            |spi, delay| program_csd(spi, delay, &self.waits, &csd),
+        )
+        .context(IOSnafu {})?;
+
+        self.csd = csd;
+        Ok(())
+    }
+
+    /// Set the password of the card to `new`, replacing the current password
+    /// `old` (which is empty if the card has no password).
+    ///
+    /// Each password is at most 16 bytes long. Setting a password does not
+    /// lock the card, but the card is locked each time it is powered up
+    /// until it is unlocked with [`SDCard::unlock`].
+    pub fn set_password(&mut self, old: &[u8], new: &[u8]) -> Result<(), IOError> {
+        self.lock_unlock(LockUnlockOp::SetPassword { old, new })
+    }
+
+    /// Clear the password of the card.
+    pub fn clear_password(&mut self, password: &[u8]) -> Result<(), IOError> {
+        self.lock_unlock(LockUnlockOp::ClearPassword(password))
+    }
+
+    /// Lock the card with its password.
+    ///
+    /// A locked card refuses to read or write any blocks until it is
+    /// unlocked.
+    pub fn lock(&mut self, password: &[u8]) -> Result<(), IOError> {
+        self.lock_unlock(LockUnlockOp::Lock(password))
+    }
+
+    /// Unlock the card with its password.
+    pub fn unlock(&mut self, password: &[u8]) -> Result<(), IOError> {
+        self.lock_unlock(LockUnlockOp::Unlock(password))
+    }
+
+    /// Erase the whole card along with its password.
+    ///
+    /// This is the only way to use a locked card whose password is lost. It
+    /// can take up to 3 minutes to complete.
+    pub fn force_erase(&mut self) -> Result<(), IOError> {
+        self.lock_unlock(LockUnlockOp::ForceErase)
+    }
+
+    fn lock_unlock(&mut self, op: LockUnlockOp<'_>) -> Result<(), IOError> {
+        self.locked = with_cs_low(
+            &mut self.cs,
+            &mut self.spi,
+            &mut self.delay,
+            |spi, delay| lock_unlock(spi, delay, &self.waits, op),
+        )
+        .context(IOSnafu {})?;
+
+        Ok(())
+    }
+
+    /// Set the clock rate of the `SPI` interface to `hz`, or to the maximum
+    /// clock rate of the card if that is slower.
+    ///
+    /// This does nothing unless the `SDCard` was created with clock rates
+    /// (see [`SDCardConfig::clock_rates`]).
+    pub fn set_clock_rate(&mut self, hz: u32) {
+        if let Some(clock) = self.clock.as_mut() {
+            clock.set(&mut self.spi, hz);
+        }
+    }
+
+    /// Run `f` with the chip select asserted, stepping down the clock rate
+    /// and retrying `f` when it fails with CRC errors too many times in a
+    /// row.
+    fn with_clock_retry<O>(
+        &mut self,
+        mut f: impl FnMut(
+            &mut SPI,
+            &mut TimedDelay<DELAY, TIMER>,
+            &CommandWaits,
+        ) -> Result<O, transactions::Error>,
+    ) -> Result<O, IOError> {
+        let mut crc_errors = 0;
+
+        loop {
+            let waits = &self.waits;
+            let result = with_cs_low(
+                &mut self.cs,
+                &mut self.spi,
+                &mut self.delay,
+                |spi, delay| f(spi, delay, waits),
+            );
+
+            match (result, self.clock.as_mut()) {
+                (Err(e), Some(clock)) if e.is_crc_error() => {
+                    crc_errors += 1;
+                    if crc_errors == MAX_CRC_ERRORS {
+                        if !clock.step_down(&mut self.spi) {
+                            return Err(IOSnafu {}.into_error(e));
+                        }
+                        crc_errors = 0;
+                    }
+                }
+                (result, _) => return result.context(IOSnafu {}),
+            }
+        }
+    }
+
+    fn check_unlocked(&self) -> Result<(), IOError> {
+        if self.locked {
+            Err(IOSnafu {}.into_error(transactions::Error::CardStatus {
+                source: CardStatusError::CardLocked,
+            }))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_not_write_protected(&self) -> Result<(), IOError> {
+        if self.csd.is_write_protected() {
+            Err(IOSnafu {}.into_error(transactions::Error::WriteProtected))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check that the `count` blocks starting with the block at index
+    /// `block` are all on the card.
+    fn check_block_range(&self, block: u32, count: u64) -> Result<(), IOError> {
+        let num_blocks = self.num_blocks();
+
+        if u64::from(block) + count <= u64::from(num_blocks) {
+            Ok(())
+        } else {
+            let block = cmp::max(block, num_blocks);
+            Err(IOSnafu {}.into_error(transactions::Error::BlockOutOfRange { block }))
+        }
+    }
+
+    /// Check that the blocks that the `len` bytes starting at byte `offset`
+    /// overlap are all on the card.
+    fn check_byte_range(&self, offset: u32, len: usize) -> Result<(), IOError> {
+        if len == 0 {
+            return Ok(());
+        }
+
+        let first = offset / BLOCK_SIZE as u32;
+        let last = (u64::from(offset) + len as u64 - 1) / BLOCK_SIZE as u64;
+        self.check_block_range(first, last - u64::from(first) + 1)
+    }
+
+    fn check_write_protect_groups(&self) -> Result<(), IOError> {
+        match self.csd.write_protect_group_size() {
+            Some(_) => Ok(()),
+            None => Err(IOSnafu {}.into_error(transactions::Error::UnsupportedWriteProtect)),
+        }
+    }
+
+    /// Read the card status and check it for errors.
+    ///
+    /// This is useful after a write or when an operation failed for no
+    /// apparent reason. An error in the card status is reported through
+    /// [`IOError::card_status`].
+    pub fn status(&mut self) -> Result<(), IOError> {
+        with_cs_low(
+            &mut self.cs,
+            &mut self.spi,
+            &mut self.delay,
+            |spi, delay| send_status(spi, delay, &self.waits),
+        )
+        .context(IOSnafu {})
+    }
+}
+
+impl<SPI, CS, DELAY, TIMER, CLOCK> SDCard<SPI, CS, DELAY, TIMER, CLOCK> {
+    /// The number of blocks on the card.
+    ///
+    /// This is calculated from the CSD register of the card.
+    pub fn num_blocks(&self) -> u32 {
+        self.csd.block_count()
+    }
+
+    /// The programmable part of the CSD register of the card.
+    ///
+    /// See [`SDCard::program_csd`] to change it.
+    pub fn programmable_csd(&self) -> ProgrammableCsd {
+        self.csd.programmable()
+    }
+
+    /// The current clock rate of the `SPI` interface in Hz.
+    ///
+    /// This is `None` unless the `SDCard` was created with clock rates (see
+    /// [`SDCardConfig::clock_rates`]) and one of its clock rates has been
+    /// set.
+    pub fn clock_rate(&self) -> Option<u32> {
+        self.clock.as_ref().and_then(|clock| clock.current())
+    }
+
+    /// Whether the card is locked by a password.
+    ///
+    /// A card with a password is locked when it is powered up. See
+    /// [`SDCard::unlock`] to unlock it.
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Whether the whole card is write protected, either temporarily or
+    /// permanently, by its CSD register.
+    pub fn is_write_protected(&self) -> bool {
+        self.csd.is_write_protected()
+    }
+
+    /// The number of blocks in a write protect group.
+    ///
+    /// This is `None` if the card does not support write protect groups,
+    /// which is only supported by standard capacity (SDSC) cards.
+    pub fn write_protect_group_size(&self) -> Option<u32> {
+        self.csd.write_protect_group_size()
+    }
+
+    /// The type of the card.
+    pub fn card_type(&self) -> CardType {
+        self.properties.card_type
+    }
+
+    /// A description of the card, such as for logging or for diagnostics.
+    pub fn info(&self) -> CardInfo {
+        CardInfo {
+            version: self.properties.version,
+            capacity_class: self.csd.capacity_class(),
+            block_count: self.csd.block_count(),
+            voltage_window: self.properties.voltage_window,
+            crc_enabled: self.properties.crc_enabled,
+        }
+    }
+
+    /// The number of I/O functions of the card.
+    ///
+    /// This is 0 unless the card is a combo card, which has an SDIO portion
+    /// as well as a memory portion. Only the memory portion of a combo card
+    /// is used.
+    pub fn io_functions(&self) -> u8 {
+        self.properties.io_functions
+    }
+
+    /// The version of the MultiMediaCard System Specification that the card
+    /// complies with (SPEC_VERS in the CSD register).
+    ///
+    /// This is `None` unless the card is an MMC card.
+    pub fn mmc_spec_version(&self) -> Option<u8> {
+        self.csd.mmc_spec_version()
+    }
+
+    /// The card identification (CID) register of the card.
+    ///
+    /// This identifies the physical card through its manufacturer, product
+    /// name and serial number. The fields are decoded with the layout of an
+    /// SD card, which differs from the layout for an MMC card.
+    pub fn cid(&self) -> &Cid {
+        &self.cid
+    }
+
+    /// The SD card configuration register (SCR) of the card.
+    ///
+    /// This reports the optional features that the card supports. It is
+    /// `None` for an MMC card, which does not have an SCR.
+    pub fn scr(&self) -> Option<&Scr> {
+        self.scr.as_ref()
+    }
+
+    /// The SD Status of the card.
+    ///
+    /// This reports the speed class, the allocation unit size and the erase
+    /// timing of the card. It is read once when the card is initialized. It
+    /// is `None` for an MMC card, which does not have an SD Status.
+    pub fn sd_status(&self) -> Option<&SdStatus> {
+        self.sd_status.as_ref()
+    }
+
     /// Consume the `SDCard` and return the underlying `SPI` and chip select.
+    ///
+    /// The timer, if there is one, is dropped.
     pub fn release(self) -> (SPI, CS, DELAY) {
-        (self.spi, self.cs, self.delay)
+        (self.spi, self.cs, self.delay.delay)
     }
 }
 
@@ -123,27 +756,142 @@ impl<SPI: Debug, CS: Debug> InitilizationError<SPI, CS> {
     pub fn release(self) -> (SPI, CS) {
         (self.spi, self.cs)
     }
+
+    /// The voltage window that the card supports.
+    ///
+    /// This is `None` unless the initilization failed because the supply
+    /// voltage of the host is outside of the voltage window of the card.
+    pub fn card_voltage_window(&self) -> Option<VoltageWindow> {
+        match self.source {
+            transactions::Error::UnsupportedVoltage { window, .. } => Some(window),
+            _ => None,
+        }
+    }
+
+    /// The type of the card if it cannot be used as a memory card.
+    ///
+    /// This is `None` unless the initilization failed because the card is
+    /// of a type that is not supported, such as an SDIO card without a
+    /// memory portion or a high capacity MMC card in sector addressing mode.
+    pub fn unsupported_card_type(&self) -> Option<UnsupportedCardType> {
+        match self.source {
+            transactions::Error::UnsupportedCard { card_type } => Some(card_type),
+            _ => None,
+        }
+    }
+
+    /// The time (in microseconds) that the initilization waited for the card
+    /// before it timed out.
+    ///
+    /// This is `None` unless the initilization failed because the card was
+    /// still initializing after the SdSendOpCond timeout of 1 s (see
+    /// [`SDCardConfig::op_cond_timeout`]), or because the card was not ready
+    /// for a command or did not respond to it in time (see
+    /// [`SDCardConfig::ready_timeout`] and
+    /// [`SDCardConfig::response_timeout`]).
+    pub fn elapsed_us(&self) -> Option<u32> {
+        self.source.elapsed_us()
+    }
 }
 
 /// The error type for [`SDCard`] IO operations.
 #[derive(Debug, Snafu)]
-pub struct IOError {}
+#[snafu(display("Unable to read from or write to the SD Card."))]
+pub struct IOError {
+    source: transactions::Error,
+}
 
-impl<SPI, CS, DELAY> Storage for SDCard<SPI, CS, DELAY> {
-    fn write(&mut self, _offset: u32, _bytes: &[u8]) -> Result<(), Self::Error> {
-        todo!();
+impl IOError {
+    /// The number of data blocks that the card reports as written before a
+    /// multiple block write failed.
+    ///
+    /// The count is relative to the first data block of the write. This is
+    /// `None` unless the card rejected a data block during a write that
+    /// covered more than one data block.
+    pub fn blocks_written(&self) -> Option<u32> {
+        match self.source {
+            transactions::Error::PartialWrite { blocks_written, .. } => Some(blocks_written),
+            _ => None,
+        }
+    }
+
+    /// The error that the card reported in its status, if any.
+    ///
+    /// This is `None` unless the IO operation failed because the card
+    /// reported an error in its status (see [`SDCard::status`]). A write to a
+    /// write protected block is reported as [`CardStatusError::WpViolation`]
+    /// and a read or write while the card is locked is reported as
+    /// [`CardStatusError::CardLocked`].
+    pub fn card_status(&self) -> Option<CardStatusError> {
+        match self.source {
+            transactions::Error::CardStatus { source } => Some(source),
+            _ => None,
+        }
+    }
+
+    /// The time (in microseconds) that the IO operation waited for the card
+    /// before it timed out.
+    ///
+    /// This is `None` unless the IO operation failed because the card took
+    /// too long to send a data block (100 ms) or was busy for too long after
+    /// a data block was written to it (250 ms for an SDSC card and 500 ms for
+    /// an SDHC or SDXC card). It is also set when the card was not ready for
+    /// a command or did not respond to it in time (see
+    /// [`SDCardConfig::ready_timeout`] and
+    /// [`SDCardConfig::response_timeout`]).
+    pub fn elapsed_us(&self) -> Option<u32> {
+        self.source.elapsed_us()
     }
 }
 
-impl<SPI, CS, DELAY> ReadStorage for SDCard<SPI, CS, DELAY> {
+impl<SPI, CS, DELAY, TIMER, CLOCK> Storage for SDCard<SPI, CS, DELAY, TIMER, CLOCK>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    CS: OutputPin,
+    DELAY: DelayUs<u16>,
+    TIMER: Timer,
+    CLOCK: SpiClock<SPI>,
+{
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.check_unlocked()?;
+        self.check_not_write_protected()?;
+        self.check_byte_range(offset, bytes.len())?;
+        let capacity = self.properties.capacity;
+        let card_type = self.properties.card_type;
+
+        self.with_clock_retry(|spi, delay, waits| {
+            write(spi, delay, waits, capacity, card_type, offset, bytes)
+        })
+    }
+}
+
+impl<SPI, CS, DELAY, TIMER, CLOCK> ReadStorage for SDCard<SPI, CS, DELAY, TIMER, CLOCK>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    CS: OutputPin,
+    DELAY: DelayUs<u16>,
+    TIMER: Timer,
+    CLOCK: SpiClock<SPI>,
+{
     type Error = IOError;
 
-    fn read(&mut self, _offset: u32, _bytes: &mut [u8]) -> Result<(), Self::Error> {
-        todo!()
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        self.check_unlocked()?;
+        self.check_byte_range(offset, bytes.len())?;
+        let capacity = self.properties.capacity;
+
+        self.with_clock_retry(|spi, delay, waits| {
+            read(spi, delay, waits, capacity, offset, &mut *bytes)
+        })
     }
 
+    /// The capacity of the card in bytes.
+    ///
+    /// This is calculated from the CSD register of the card. The capacity of
+    /// a card that is too large to be represented in a `usize` is reported as
+    /// `usize::MAX`.
     fn capacity(&self) -> usize {
-        todo!()
+        self.csd.capacity().try_into().unwrap_or(usize::MAX)
     }
 }
 
@@ -153,10 +901,33 @@ mod tests {
 
     use embedded_hal_mock::delay;
 
-    use crate::testutils::{FakeCard, StubPin};
+    use crate::common::CardCapacity;
+    use crate::testutils::{
+        FakeCard, FakeCardType, StepTimer, StubPin, FAKE_CID, FAKE_CSD, FAKE_SCR, FAKE_SD_STATUS,
+    };
 
     use super::*;
 
+    /// Initialize an [`SDCard`] for the fake `card`.
+    fn new_card(card: FakeCard) -> SDCard<FakeCard, StubPin, delay::MockNoop> {
+        SDCard::new(card, StubPin, delay::MockNoop::new()).expect("error initilizing the card")
+    }
+
+    /// Initialize an [`SDCard`] for the fake `card` using the settings from
+    /// `config`.
+    fn new_card_with_config<TIMER, INCREASE, CLOCK>(
+        card: FakeCard,
+        config: SDCardConfig<FakeCard, TIMER, INCREASE, CLOCK>,
+    ) -> SDCard<FakeCard, StubPin, delay::MockNoop, TIMER, CLOCK>
+    where
+        TIMER: Timer,
+        INCREASE: FnOnce(FakeCard, u32) -> FakeCard,
+        CLOCK: SpiClock<FakeCard>,
+    {
+        SDCard::with_config(card, StubPin, delay::MockNoop::new(), config)
+            .expect("error initilizing the card")
+    }
+
     #[test]
     fn sd_card_with_speed_increase_increases_speed() {
         let mut increased = false;
@@ -174,6 +945,434 @@ mod tests {
         );
     }
 
+    #[test]
+    fn sd_card_new_for_mmc_card_is_mmc() {
+        let sut = new_card(FakeCard::builder().card_type(FakeCardType::Mmc).build());
+
+        assert_eq!(sut.card_type(), CardType::Mmc);
+        assert_eq!(sut.mmc_spec_version(), Some(3));
+        assert_eq!(sut.num_blocks(), 4 * 1024 * 1024);
+        assert!(sut.scr().is_none());
+        assert!(sut.sd_status().is_none());
+    }
+
+    #[test]
+    fn sd_card_erase_of_whole_erase_groups_on_mmc_card_is_ok() {
+        let mut sut = new_card(FakeCard::builder().card_type(FakeCardType::Mmc).build());
+
+        assert!(sut.erase(32, 95, EraseKind::Erase).is_ok());
+    }
+
+    #[test]
+    fn sd_card_erase_of_partial_erase_group_on_mmc_card_is_error() {
+        let mut sut = new_card(FakeCard::builder().card_type(FakeCardType::Mmc).build());
+
+        let result = sut.erase(0, 1, EraseKind::Erase);
+
+        assert!(matches!(
+            result.map_err(|e| e.source),
+            Err(transactions::Error::MisalignedErase { group_size: 32 })
+        ));
+    }
+
+    #[test]
+    fn sd_card_discard_on_mmc_card_is_unsupported() {
+        let mut sut = new_card(FakeCard::builder().card_type(FakeCardType::Mmc).build());
+
+        let result = sut.erase(0, 31, EraseKind::Discard);
+
+        assert!(matches!(
+            result.map_err(|e| e.source),
+            Err(transactions::Error::UnsupportedErase {
+                kind: EraseKind::Discard
+            })
+        ));
+    }
+
+    #[test]
+    fn sd_card_new_for_sdio_card_is_unsupported() {
+        let delay = delay::MockNoop::new();
+
+        let result = SDCard::new(
+            FakeCard::builder().card_type(FakeCardType::Sdio).build(),
+            StubPin,
+            delay,
+        );
+
+        assert_eq!(
+            result
+                .err()
+                .expect("SDIO card initilized as a memory card")
+                .unsupported_card_type(),
+            Some(UnsupportedCardType::Sdio)
+        );
+    }
+
+    #[test]
+    fn sd_card_new_for_combo_card_initilizes_memory() {
+        let sut = new_card(FakeCard::builder().card_type(FakeCardType::Combo).build());
+
+        assert_eq!(sut.card_type(), CardType::Sd);
+        assert_eq!(sut.io_functions(), 1);
+    }
+
+    #[test]
+    fn sd_card_info_describes_card() {
+        let sut = new_card(FakeCard::default());
+        let info = sut.info();
+
+        assert_eq!(info.version, Version::V2);
+        assert_eq!(info.capacity_class, CapacityClass::Sdhc);
+        assert_eq!(info.block_count, 0x3b38 * 1024);
+        assert_eq!(info.voltage_window.min_millivolts(), Some(2_700));
+        assert_eq!(info.voltage_window.max_millivolts(), Some(3_600));
+        assert!(info.crc_enabled);
+    }
+
+    #[test]
+    fn sd_card_info_for_mmc_card_is_v1_sdsc() {
+        let sut = new_card(FakeCard::builder().card_type(FakeCardType::Mmc).build());
+        let info = sut.info();
+
+        assert_eq!(info.version, Version::V1);
+        assert_eq!(info.capacity_class, CapacityClass::Sdsc);
+    }
+
+    #[test]
+    fn sd_card_with_config_uses_config() {
+        let config = SDCardConfig::new()
+            .op_cond_timeout(2_000_000, 100)
+            .ready_timeout(5_000, 50)
+            .crc(CrcOption::Off)
+            .speed_increase(|mut spi: FakeCard, hz| {
+                spi.set_clock_rate(hz);
+                spi
+            });
+
+        let sut = new_card_with_config(FakeCard::default(), config);
+
+        assert!(!sut.info().crc_enabled);
+        assert_eq!(sut.waits.ready_timeout, 5_000);
+        assert_eq!(sut.waits.ready_delay, 50);
+        let (spi, _, _) = sut.release();
+        assert_eq!(spi.clock_rate(), Some(50_000_000));
+    }
+
+    #[test]
+    fn sd_card_with_config_uses_capturing_speed_increase() {
+        let divisor = 2;
+        let config = SDCardConfig::new().speed_increase(move |mut spi: FakeCard, hz| {
+            spi.set_clock_rate(hz / divisor);
+            spi
+        });
+
+        let sut = new_card_with_config(FakeCard::default(), config);
+
+        let (spi, _, _) = sut.release();
+        assert_eq!(spi.clock_rate(), Some(25_000_000));
+    }
+
+    #[test]
+    fn sd_card_with_config_timer_reads_block() {
+        let config = SDCardConfig::new().timer(StepTimer::new(0, 10));
+        let mut blocks = [[0xa5; BLOCK_SIZE]];
+
+        let mut sut = new_card_with_config(FakeCard::default(), config);
+        let result = sut.read_blocks(0, &mut blocks);
+
+        assert!(result.is_ok());
+        assert_eq!(blocks, [[0; BLOCK_SIZE]]);
+    }
+
+    #[test]
+    fn sd_card_with_config_timer_waits_and_clock_rates_steps_down_clock_rate() {
+        let config = SDCardConfig::new()
+            .timer(StepTimer::new(0, 10))
+            .ready_timeout(5_000, 20)
+            .response_timeout(2_000)
+            .clock_rates([25_000_000, 12_500_000], FakeCard::set_clock_rate);
+        let mut blocks = [[0xa5; BLOCK_SIZE]];
+
+        let mut sut =
+            new_card_with_config(FakeCard::builder().noisy_above(20_000_000).build(), config);
+        let result = sut.read_blocks(0, &mut blocks);
+
+        assert!(result.is_ok());
+        assert_eq!(blocks, [[0; BLOCK_SIZE]]);
+        assert_eq!(sut.clock_rate(), Some(12_500_000));
+        assert_eq!(sut.waits.ready_timeout, 5_000);
+        assert_eq!(sut.waits.ready_delay, 20);
+        assert_eq!(sut.waits.response_timeout, 2_000);
+    }
+
+    #[test]
+    fn sd_card_with_config_host_voltage_outside_window_is_error() {
+        let delay = delay::MockNoop::new();
+        let config = SDCardConfig::new().host_voltage(1_800);
+
+        let result = SDCard::with_config(FakeCard::default(), StubPin, delay, config);
+
+        let window = result
+            .err()
+            .expect("card initilized with an unsupported voltage")
+            .card_voltage_window()
+            .expect("initilization error is not for the voltage");
+        assert_eq!(window.min_millivolts(), Some(2_700));
+        assert_eq!(window.max_millivolts(), Some(3_600));
+    }
+
+    #[test]
+    fn sd_card_capacity_is_from_csd() {
+        let sut = new_card(FakeCard::default());
+
+        assert_eq!(sut.capacity(), 0x3b38 * 512 * 1024);
+    }
+
+    #[test]
+    fn sd_card_num_blocks_is_from_csd() {
+        let sut = new_card(FakeCard::default());
+
+        assert_eq!(sut.num_blocks(), 0x3b38 * 1024);
+    }
+
+    #[test]
+    fn sd_card_cid_is_read_during_initilization() {
+        let sut = new_card(FakeCard::default());
+
+        assert_eq!(sut.cid().serial_number(), 0x1234_5678);
+    }
+
+    #[test]
+    fn sd_card_scr_is_read_during_initilization() {
+        let sut = new_card(FakeCard::default());
+
+        assert_eq!(
+            sut.scr().expect("card has no SCR").spec_version(),
+            Some((3, 0))
+        );
+    }
+
+    #[test]
+    fn sd_card_sd_status_is_read_during_initilization() {
+        let sut = new_card(FakeCard::default());
+
+        assert_eq!(
+            sut.sd_status()
+                .expect("card has no SD Status")
+                .speed_class(),
+            Some(10)
+        );
+    }
+
+    #[test]
+    fn sd_card_erase_is_ok() {
+        let mut sut = new_card(FakeCard::default());
+
+        assert!(sut.erase(0, 7, EraseKind::Erase).is_ok());
+    }
+
+    #[test]
+    fn sd_card_read_blocks_past_end_of_card_is_error() {
+        let mut blocks = [[0; BLOCK_SIZE]; 2];
+
+        let mut sut = new_card(FakeCard::default());
+        let last = sut.num_blocks() - 1;
+        let result = sut.read_blocks(last, &mut blocks);
+
+        assert!(matches!(
+            result.map_err(|e| e.source),
+            Err(transactions::Error::BlockOutOfRange { block }) if block == last + 1
+        ));
+    }
+
+    #[test]
+    fn sd_card_write_blocks_at_max_lba_is_error() {
+        let blocks = [[0; BLOCK_SIZE]];
+
+        let mut sut = new_card(FakeCard::default());
+        let result = sut.write_blocks(u32::MAX, &blocks);
+
+        assert!(matches!(
+            result.map_err(|e| e.source),
+            Err(transactions::Error::BlockOutOfRange { block: u32::MAX })
+        ));
+    }
+
+    #[test]
+    fn sd_card_read_past_end_of_card_is_error() {
+        // The end of the 2 GiB MMC card is within reach of a byte offset.
+        let mut sut = new_card(FakeCard::builder().card_type(FakeCardType::Mmc).build());
+        let end = sut.num_blocks();
+        let offset = (end - 1) * BLOCK_SIZE as u32 + 4;
+
+        let result = sut.read(offset, &mut [0; BLOCK_SIZE]);
+
+        assert!(matches!(
+            result.map_err(|e| e.source),
+            Err(transactions::Error::BlockOutOfRange { block }) if block == end
+        ));
+    }
+
+    #[test]
+    fn sd_card_write_past_end_of_card_is_error() {
+        // The end of the 2 GiB MMC card is within reach of a byte offset.
+        let mut sut = new_card(FakeCard::builder().card_type(FakeCardType::Mmc).build());
+        let end = sut.num_blocks();
+        let offset = (end - 1) * BLOCK_SIZE as u32;
+
+        let result = sut.write(offset, &[0x42; 2 * BLOCK_SIZE]);
+
+        assert!(matches!(
+            result.map_err(|e| e.source),
+            Err(transactions::Error::BlockOutOfRange { block }) if block == end
+        ));
+    }
+
+    #[test]
+    fn sd_card_erase_past_end_of_card_is_error() {
+        let mut sut = new_card(FakeCard::default());
+        let end = sut.num_blocks();
+
+        assert!(sut.erase(0, end, EraseKind::Erase).is_err());
+    }
+
+    #[test]
+    fn sd_card_erase_with_end_before_start_is_error() {
+        let mut sut = new_card(FakeCard::default());
+        let result = sut.erase(7, 0, EraseKind::Erase);
+
+        assert!(matches!(
+            result.map_err(|e| e.source),
+            Err(transactions::Error::InvalidEraseRange)
+        ));
+    }
+
+    #[test]
+    fn sd_card_erase_on_locked_card_is_error() {
+        let mut sut = new_card(FakeCard::builder().locked(true).build());
+        let result = sut.erase(0, 7, EraseKind::Erase);
+
+        assert_eq!(
+            result.expect_err("erase of a locked card").card_status(),
+            Some(CardStatusError::CardLocked)
+        );
+    }
+
+    #[test]
+    fn sd_card_discard_is_ok_when_supported() {
+        let mut sut = new_card(FakeCard::default());
+
+        assert!(sut.erase(0, 7, EraseKind::Discard).is_ok());
+    }
+
+    #[test]
+    fn sd_card_fule_is_error_when_unsupported() {
+        let mut sut = new_card(FakeCard::default());
+
+        assert!(sut.erase(0, 7, EraseKind::Fule).is_err());
+    }
+
+    #[test]
+    fn sd_card_set_write_protect_is_error_without_write_protect_groups() {
+        let mut sut = new_card(FakeCard::default());
+
+        assert_eq!(sut.write_protect_group_size(), None);
+        assert!(sut.set_write_protect(0).is_err());
+    }
+
+    #[test]
+    fn sd_card_write_is_refused_after_temporary_write_protect() {
+        let mut sut = new_card(FakeCard::default());
+        sut.program_csd(ProgrammableCsd {
+            temporary_write_protect: true,
+            ..sut.programmable_csd()
+        })
+        .expect("error programming the CSD");
+
+        assert!(sut.is_write_protected());
+        assert!(sut.write(0, &[0x42; 4]).is_err());
+    }
+
+    #[test]
+    fn sd_card_locked_card_initilizes_and_refuses_reads() {
+        let mut sut = new_card(FakeCard::builder().locked(true).build());
+        let result = sut.read(0, &mut [0; 4]);
+
+        assert!(sut.is_locked());
+        assert_eq!(
+            result.expect_err("read from a locked card").card_status(),
+            Some(CardStatusError::CardLocked)
+        );
+    }
+
+    #[test]
+    fn sd_card_unlock_unlocks_card() {
+        let mut sut = new_card(FakeCard::builder().locked(true).build());
+        sut.unlock(b"password").expect("error unlocking the card");
+
+        assert!(!sut.is_locked());
+    }
+
+    #[test]
+    fn sd_card_with_clock_rates_sets_fastest_supported_rate() {
+        let config = SDCardConfig::new().clock_rates(
+            [100_000_000, 40_000_000, 10_000_000],
+            FakeCard::set_clock_rate,
+        );
+
+        let sut = new_card_with_config(FakeCard::default(), config);
+
+        assert_eq!(sut.clock_rate(), Some(40_000_000));
+        assert_eq!(sut.release().0.clock_rate(), Some(40_000_000));
+    }
+
+    #[test]
+    fn sd_card_with_capturing_clock_rate_setter_calls_setter() {
+        let mut rates_set = Vec::new();
+        let config =
+            SDCardConfig::new().clock_rates(vec![25_000_000], |card: &mut FakeCard, hz| {
+                rates_set.push(hz);
+                card.set_clock_rate(hz);
+            });
+
+        let sut = new_card_with_config(FakeCard::default(), config);
+        drop(sut);
+
+        assert_eq!(rates_set, [25_000_000]);
+    }
+
+    #[test]
+    fn sd_card_read_with_crc_errors_steps_down_clock_rate() {
+        let config =
+            SDCardConfig::new().clock_rates([25_000_000, 12_500_000], FakeCard::set_clock_rate);
+        let mut sut =
+            new_card_with_config(FakeCard::builder().noisy_above(20_000_000).build(), config);
+
+        let result = sut.read(0, &mut [0; 4]);
+
+        assert!(result.is_ok());
+        assert_eq!(sut.clock_rate(), Some(12_500_000));
+    }
+
+    #[test]
+    fn sd_card_read_with_crc_errors_at_slowest_rate_is_error() {
+        let config = SDCardConfig::new().clock_rates([25_000_000], FakeCard::set_clock_rate);
+        let mut sut =
+            new_card_with_config(FakeCard::builder().noisy_above(20_000_000).build(), config);
+
+        let result = sut.read(0, &mut [0; 4]);
+
+        assert!(result.is_err());
+        assert_eq!(sut.clock_rate(), Some(25_000_000));
+    }
+
+    #[test]
+    fn sd_card_status_with_clear_status_is_ok() {
+        let mut sut = new_card(FakeCard::default());
+
+        assert!(sut.status().is_ok());
+    }
+
     #[test]
     fn sd_card_release_returns_contained_resourses() {
         let spi = Arc::new(5);
@@ -183,8 +1382,25 @@ mod tests {
         let sut = SDCard {
             spi: spi.clone(),
             cs: cs.clone(),
-            delay: delay.clone(),
-            capacity: CardCapacity::Standard,
+            delay: TimedDelay {
+                delay: delay.clone(),
+                timer: None::<NoTimer>,
+            },
+            waits: CommandWaits::DEFAULT,
+            properties: CardProperties {
+                card_type: CardType::Sd,
+                capacity: CardCapacity::Standard,
+                io_functions: 0,
+                version: Version::V2,
+                voltage_window: VoltageWindow::new(0x1ff),
+                crc_enabled: true,
+            },
+            csd: Csd::new(FAKE_CSD).unwrap(),
+            cid: Cid::new(FAKE_CID).unwrap(),
+            scr: Some(Scr::new(FAKE_SCR).unwrap()),
+            sd_status: Some(SdStatus::new(FAKE_SD_STATUS)),
+            locked: false,
+            clock: None::<ClockControl<NoSpiClock>>,
         };
         let (rel_spi, rel_cs, rel_delay) = sut.release();
 
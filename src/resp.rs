@@ -18,18 +18,33 @@
 //! be sent from the card.
 //!
 //! The non-R1 responses currently implmented are:
+//!     - R1b
+//!     - R2
 //!     - R3
+//!     - R4
 //!     - R7
 //!
-//! The non-R1 responses that are not yet implemented are:
-//!     - R1b
-//!     - R2
-
-use core::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign};
+//! Some commands are answered with a data block holding the contents of a
+//! card register rather than with a longer response. The registers that are
+//! currently decoded are:
+//!     - CID
+//!     - CSD
+//!     - SCR
+//!     - SD Status
+//!     - Switch function status
+
+use core::{
+    cmp,
+    ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign},
+};
 
 use snafu::{ensure, Snafu};
 
-use crate::common::{CardCapacity, VOLTAGE_2_7_TO_3_6};
+use crate::common::{crc7, CardCapacity, CardType, BLOCK_SIZE, VOLTAGE_2_7_TO_3_6};
+
+/// The default time in microseconds that a card may take to erase each
+/// allocation unit (see section 4.14 of the Simplified Specification).
+const DEFAULT_AU_ERASE_TIMEOUT: u64 = 250_000;
 
 /// Newtype to support decoding of an R1 response.
 ///
@@ -37,6 +52,16 @@ use crate::common::{CardCapacity, VOLTAGE_2_7_TO_3_6};
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct R1Response(u8);
 
+/// Newtype to support an R1b response.
+///
+/// An R1b response is an R1 response that is followed by the card signaling
+/// busy by holding the data out line low. The response itself is decoded with
+/// [`R1Response`]; the busy signal is handled when the command is executed.
+///
+/// This type is based on section 7.3.2.2 of the Simplified Specification.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct R1bResponse(R1Response);
+
 /// Newtype to support decoding of an R7 response.
 ///
 /// This type decodes the last 4 bytes of the R7 response. The first byte
@@ -48,6 +73,17 @@ pub struct R1Response(u8);
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct R7Response(u32, R1Response);
 
+/// Newtype to support decoding of an R2 response.
+///
+/// This type decodes the second byte of the R2 response. The first byte
+/// is an R1 response that should be decoded with [`R1Response`]. The second
+/// byte of the R2 response will not be present if
+/// [`R1Response::response_truncated`] is true.
+///
+/// This type is based on section 7.3.2.3 of the Simplified Specification.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct R2Response(u8, R1Response);
+
 /// Newtype to support decoding the R3 response (and the OCR register).
 ///
 /// This type decodes the last 4 bytes of the R3 response. The first byte
@@ -59,6 +95,161 @@ pub struct R7Response(u32, R1Response);
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct R3Response(u32, R1Response);
 
+/// Newtype to support decoding of an R4 response.
+///
+/// This type decodes the last 4 bytes of the R4 response to an IoSendOpCond
+/// command. The first byte is an R1 response that should be decoded with
+/// [`R1Response`]. The remaining bytes of the R4 response (after the R1 byte)
+/// will not be present if [`R1Response::response_truncated`] is true.
+///
+/// This type is based on section 5.2 of the SDIO Simplified Specification.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct R4Response(u32, R1Response);
+
+/// The range of supply voltages that an SD Card supports.
+///
+/// This is the voltage window from the OCR register of the card, which is
+/// made up of 100 mV wide ranges from 2.7 V to 3.6 V.
+///
+/// This type is based on section 5.1 of the Simplified Specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VoltageWindow(u16);
+
+/// Newtype to support decoding of the CSD register.
+///
+/// The CSD register is sent by the card in a 16 byte data block in response
+/// to a SendCSD command. Only CSD Version 1.0 (SDSC cards) and CSD Version 2.0
+/// (SDHC and SDXC cards) are supported because CSD Version 3.0 is only used by
+/// SDUC cards, which do not support SPI mode. The CSD register of an MMC card
+/// has a different layout, so the type of card is kept with the register.
+///
+/// This type is based on section 5.3 of the Simplified Specification and on
+/// the CSD register of the MultiMediaCard System Specification.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Csd([u8; 16], CardType);
+
+/// The programmable part of the CSD register of an SD Card.
+///
+/// These are the only parts of the CSD register that the host can change.
+/// They are available through
+/// [`SDCard::programmable_csd()`](crate::SDCard::programmable_csd) and are
+/// changed through [`SDCard::program_csd()`](crate::SDCard::program_csd).
+///
+/// This type is based on section 5.3.2 of the Simplified Specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgrammableCsd {
+    /// The file format group (FILE_FORMAT_GRP).
+    pub file_format_group: bool,
+
+    /// Whether the contents of the card are a copy (COPY).
+    ///
+    /// This can only be set once.
+    pub copy: bool,
+
+    /// Whether the whole card is permanently write protected
+    /// (PERM_WRITE_PROTECT).
+    ///
+    /// This can only be set once and can never be cleared.
+    pub permanent_write_protect: bool,
+
+    /// Whether the whole card is temporarily write protected
+    /// (TMP_WRITE_PROTECT).
+    pub temporary_write_protect: bool,
+
+    /// The file format of the card (FILE_FORMAT).
+    ///
+    /// Only the low 2 bits are used.
+    pub file_format: u8,
+}
+
+/// The card identification (CID) register of an SD Card.
+///
+/// This identifies the individual card through the manufacturer, the
+/// product and the product serial number. It is available through
+/// [`SDCard::cid()`](crate::SDCard::cid).
+///
+/// This type is based on section 5.2 of the Simplified Specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cid([u8; 16]);
+
+/// The SD card configuration register (SCR) of an SD Card.
+///
+/// This reports the optional features that the card supports. It is
+/// available through [`SDCard::scr()`](crate::SDCard::scr).
+///
+/// This type is based on section 5.6 of the Simplified Specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scr([u8; 8]);
+
+/// The security features supported by an SD Card (from SD_SECURITY in the
+/// [`Scr`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Security {
+    /// No security
+    None,
+
+    /// Security Protocol 1.01 (SDSC card)
+    Version1,
+
+    /// Security Protocol 2.00 (SDHC card)
+    Version2,
+
+    /// Security Protocol 3.xx (SDXC card)
+    Version3,
+
+    /// A reserved value for SD_SECURITY
+    Reserved(u8),
+}
+
+/// The SD Status of an SD Card.
+///
+/// This reports the status of the features that are related to the SD Memory
+/// Card proprietary features, such as the speed class, the allocation unit
+/// (AU) size and the erase timing. It is available through
+/// [`SDCard::sd_status()`](crate::SDCard::sd_status).
+///
+/// This type is based on section 4.10.2 of the Simplified Specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SdStatus([u8; 64]);
+
+/// Newtype to support decoding of the switch function status.
+///
+/// The switch function status is sent by the card in a 64 byte data block in
+/// response to a SwitchFunc command.
+///
+/// This type is based on section 4.3.10.4 of the Simplified Specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwitchStatus([u8; 64]);
+
+/// The capacity class of an SD Card.
+///
+/// MMC cards are reported as [`CapacityClass::Sdsc`] since they also use
+/// byte addressing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CapacityClass {
+    /// Standard Capacity (up to 2 GB)
+    Sdsc,
+
+    /// High Capacity (more than 2 GB and up to 32 GB)
+    Sdhc,
+
+    /// Extended Capacity (more than 32 GB and up to 2 TB)
+    Sdxc,
+}
+
+/// The structure version of a [`Csd`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CsdVersion {
+    /// CSD Version 1.0
+    V1,
+
+    /// CSD Version 2.0
+    V2,
+
+    /// The CSD of an MMC card (any CSD_STRUCTURE)
+    Mmc,
+}
+
 /// Interface to create a response type from the initial R1 byte and the
 /// remaining bytes for the response.
 pub trait Response {
@@ -67,10 +258,17 @@ pub trait Response {
     /// This should likely be [u8; N] for some N.
     type ExtraBytes: AsMut<[u8]> + Default;
 
+    /// Whether the card may signal busy after sending the response.
+    ///
+    /// If this is true then the card holds the data out line low after the
+    /// response until it has finished processing the command.
+    const BUSY: bool = false;
+
     /// Create the response from the inital r1 byte and the SIZE -1 extra
     /// bytes.
     fn create(r1: R1Response, extra_bytes: &Self::ExtraBytes) -> Self;
 
+    #[allow(dead_code)]
     fn r1(&self) -> &R1Response;
 }
 
@@ -96,6 +294,58 @@ pub enum ResponseError {
 
     #[snafu(display("SD Card responded with unexpected check pattern."))]
     CheckPatternMismatch,
+
+    #[snafu(display("SD Card has an unsupported CSD structure version."))]
+    UnsupportedCsdStructure,
+
+    #[snafu(display("The CRC for the SD Card CID register did not match."))]
+    CidCrcMismatch,
+
+    #[snafu(display("SD Card has an unsupported SCR structure version."))]
+    UnsupportedScrStructure,
+}
+
+/// The errors that the card reports in its status (the second byte of an
+/// R2 response).
+///
+/// This type is based on section 7.3.2.3 of the Simplified Specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Snafu)]
+pub enum CardStatusError {
+    /// The card is locked by the host.
+    #[snafu(display("SD Card is locked."))]
+    CardLocked,
+
+    /// The card skipped erasing write protected blocks or a lock/unlock
+    /// command failed.
+    #[snafu(display(
+        "SD Card skipped erasing write protected blocks or failed a lock/unlock command."
+    ))]
+    WpEraseSkipOrLockUnlockFailed,
+
+    /// The card reported a general or unknown error.
+    #[snafu(display("SD Card reported a general or unknown error."))]
+    CardError,
+
+    /// The card reported an internal card controller error.
+    #[snafu(display("SD Card reported an internal card controller error."))]
+    CardControllerError,
+
+    /// The card's internal ECC was unable to correct the data.
+    #[snafu(display("SD Card reported an ECC failure."))]
+    CardEccFailed,
+
+    /// The host tried to write to a write protected block.
+    #[snafu(display("SD Card detected a write to a write protected block."))]
+    WpViolation,
+
+    /// The host made an invalid selection of blocks to erase.
+    #[snafu(display("SD Card detected an invalid selection of blocks to erase."))]
+    EraseParam,
+
+    /// The argument to a command was out of range or the host tried to
+    /// change a read-only part of the CSD register.
+    #[snafu(display("SD Card detected an out of range argument or a CSD overwrite."))]
+    OutOfRange,
 }
 
 impl R1Response {
@@ -141,6 +391,71 @@ impl Response for R1Response {
     }
 }
 
+impl Response for R1bResponse {
+    type ExtraBytes = [u8; 0];
+
+    const BUSY: bool = true;
+
+    fn create(r1: R1Response, _extra_bytes: &Self::ExtraBytes) -> Self {
+        R1bResponse(r1)
+    }
+
+    fn r1(&self) -> &R1Response {
+        &self.0
+    }
+}
+
+impl R2Response {
+    pub fn check_error(&self) -> Result<(), CardStatusError> {
+        ensure!(self.0 & Self::CARD_IS_LOCKED == 0, CardLockedSnafu);
+        ensure!(
+            self.0 & Self::WP_ERASE_SKIP_LOCK_UNLOCK_FAILED == 0,
+            WpEraseSkipOrLockUnlockFailedSnafu
+        );
+        ensure!(self.0 & Self::ERROR == 0, CardSnafu);
+        ensure!(self.0 & Self::CC_ERROR == 0, CardControllerSnafu);
+        ensure!(self.0 & Self::CARD_ECC_FAILED == 0, CardEccFailedSnafu);
+        ensure!(self.0 & Self::WP_VIOLATION == 0, WpViolationSnafu);
+        ensure!(self.0 & Self::ERASE_PARAM == 0, EraseParamSnafu);
+        ensure!(self.0 & Self::OUT_OF_RANGE == 0, OutOfRangeSnafu);
+
+        Ok(())
+    }
+
+    /// Whether the card is locked by a password.
+    pub fn is_locked(&self) -> bool {
+        self.0 & Self::CARD_IS_LOCKED != 0
+    }
+
+    /// The same response with the card locked bit cleared.
+    ///
+    /// This is for commands where a locked card is not an error.
+    pub fn without_lock(self) -> Self {
+        R2Response(self.0 & !Self::CARD_IS_LOCKED, self.1)
+    }
+
+    const CARD_IS_LOCKED: u8 = 0b0000_0001;
+    const WP_ERASE_SKIP_LOCK_UNLOCK_FAILED: u8 = 0b0000_0010;
+    const ERROR: u8 = 0b0000_0100;
+    const CC_ERROR: u8 = 0b0000_1000;
+    const CARD_ECC_FAILED: u8 = 0b0001_0000;
+    const WP_VIOLATION: u8 = 0b0010_0000;
+    const ERASE_PARAM: u8 = 0b0100_0000;
+    const OUT_OF_RANGE: u8 = 0b1000_0000;
+}
+
+impl Response for R2Response {
+    type ExtraBytes = [u8; 1];
+
+    fn create(r1: R1Response, extra_bytes: &Self::ExtraBytes) -> Self {
+        R2Response(extra_bytes[0], r1)
+    }
+
+    fn r1(&self) -> &R1Response {
+        &self.1
+    }
+}
+
 impl R3Response {
     fn new(byte2: u8, byte3: u8, byte4: u8, byte5: u8, r1: R1Response) -> Self {
         let b2: u32 = byte2 as u32;
@@ -160,6 +475,11 @@ impl R3Response {
             CardCapacity::HighOrExtended
         }
     }
+
+    /// The voltage window from OCR bits 23:15.
+    pub fn voltage_window(&self) -> VoltageWindow {
+        VoltageWindow::new((self.0 >> 15) as u16)
+    }
 }
 
 impl Response for R3Response {
@@ -206,6 +526,45 @@ impl R7Response {
     }
 }
 
+impl R4Response {
+    fn new(byte2: u8, byte3: u8, byte4: u8, byte5: u8, r1: R1Response) -> Self {
+        let b2: u32 = byte2 as u32;
+        let b3: u32 = byte3 as u32;
+        let b4: u32 = byte4 as u32;
+        let b5: u32 = byte5 as u32;
+
+        R4Response((b2 << 24) | (b3 << 16) | (b4 << 8) | b5, r1)
+    }
+
+    /// The number of I/O functions of the card.
+    pub fn io_functions(&self) -> u8 {
+        ((self.0 >> 28) & 0b111) as u8
+    }
+
+    /// Whether the card also has a memory portion (a combo card).
+    pub fn memory_present(&self) -> bool {
+        self.0 & (1 << 27) != 0
+    }
+}
+
+impl Response for R4Response {
+    type ExtraBytes = [u8; 4];
+
+    fn create(r1: R1Response, extra_bytes: &Self::ExtraBytes) -> Self {
+        R4Response::new(
+            extra_bytes[0],
+            extra_bytes[1],
+            extra_bytes[2],
+            extra_bytes[3],
+            r1,
+        )
+    }
+
+    fn r1(&self) -> &R1Response {
+        &self.1
+    }
+}
+
 impl Response for R7Response {
     type ExtraBytes = [u8; 4];
 
@@ -224,6 +583,588 @@ impl Response for R7Response {
     }
 }
 
+impl VoltageWindow {
+    const LOWEST: u16 = 2_700;
+    const HIGHEST: u16 = 3_600;
+    const STEP: u16 = 100;
+
+    /// Create a voltage window from OCR bits 23:15 (in the low 9 bits of
+    /// `ranges`).
+    pub(crate) fn new(ranges: u16) -> Self {
+        VoltageWindow(ranges & 0x1ff)
+    }
+
+    /// Whether a supply voltage of `millivolts` is inside the window.
+    pub fn contains(&self, millivolts: u16) -> bool {
+        if !(Self::LOWEST..=Self::HIGHEST).contains(&millivolts) {
+            return false;
+        }
+
+        // 3.6 V is the top of the highest range rather than the bottom of a
+        // range of its own.
+        let range = cmp::min((millivolts - Self::LOWEST) / Self::STEP, 8);
+        self.0 & (1 << range) != 0
+    }
+
+    /// The lowest supply voltage in the window in mV, or `None` if the
+    /// window is empty.
+    pub fn min_millivolts(&self) -> Option<u16> {
+        (0..9)
+            .find(|range| self.0 & (1 << range) != 0)
+            .map(|range| Self::LOWEST + range * Self::STEP)
+    }
+
+    /// The highest supply voltage in the window in mV, or `None` if the
+    /// window is empty.
+    pub fn max_millivolts(&self) -> Option<u16> {
+        (0..9)
+            .rev()
+            .find(|range| self.0 & (1 << range) != 0)
+            .map(|range| Self::LOWEST + (range + 1) * Self::STEP)
+    }
+}
+
+impl Csd {
+    pub fn new(bytes: [u8; 16]) -> Result<Self, ResponseError> {
+        let csd = Csd(bytes, CardType::Sd);
+        ensure!(csd.bits(127, 126) <= 1, UnsupportedCsdStructureSnafu);
+
+        Ok(csd)
+    }
+
+    /// Decode the CSD register of an MMC card.
+    ///
+    /// A CSD_STRUCTURE of 3 means that the structure version is only given
+    /// in the EXT_CSD register, which is not supported.
+    pub fn new_mmc(bytes: [u8; 16]) -> Result<Self, ResponseError> {
+        let csd = Csd(bytes, CardType::Mmc);
+        ensure!(csd.bits(127, 126) <= 2, UnsupportedCsdStructureSnafu);
+
+        Ok(csd)
+    }
+
+    pub fn version(&self) -> CsdVersion {
+        match (self.1, self.bits(127, 126)) {
+            (CardType::Mmc, _) => CsdVersion::Mmc,
+            (CardType::Sd, 0) => CsdVersion::V1,
+            (CardType::Sd, _) => CsdVersion::V2,
+        }
+    }
+
+    /// The version of the MultiMediaCard System Specification that an MMC
+    /// card complies with (SPEC_VERS).
+    ///
+    /// This is `None` for an SD card, which has no SPEC_VERS field.
+    pub fn mmc_spec_version(&self) -> Option<u8> {
+        match self.1 {
+            CardType::Mmc => Some(self.bits(125, 122) as u8),
+            CardType::Sd => None,
+        }
+    }
+
+    /// The user data area capacity of the card in bytes.
+    ///
+    /// This uses the memory capacity calculation from section 5.3.2 for a
+    /// version 1.0 CSD and from section 5.3.3 for a version 2.0 CSD. The CSD
+    /// of an MMC card uses the same calculation as a version 1.0 CSD.
+    pub fn capacity(&self) -> u64 {
+        match self.version() {
+            CsdVersion::V1 | CsdVersion::Mmc => {
+                let c_size: u64 = self.bits(73, 62).into();
+                let c_size_mult = self.bits(49, 47);
+                let read_bl_len = self.bits(83, 80);
+
+                (c_size + 1) << (c_size_mult + 2 + read_bl_len)
+            }
+            CsdVersion::V2 => {
+                let c_size: u64 = self.bits(69, 48).into();
+
+                (c_size + 1) * 512 * 1024
+            }
+        }
+    }
+
+    /// The capacity class of the card.
+    ///
+    /// SDHC and SDXC cards both have a version 2.0 CSD, so they are told
+    /// apart by C_SIZE (see section 5.3.3).
+    pub fn capacity_class(&self) -> CapacityClass {
+        const MAX_SDHC_C_SIZE: u32 = 0x00ff5f;
+
+        match self.version() {
+            CsdVersion::V1 | CsdVersion::Mmc => CapacityClass::Sdsc,
+            CsdVersion::V2 if self.bits(69, 48) <= MAX_SDHC_C_SIZE => CapacityClass::Sdhc,
+            CsdVersion::V2 => CapacityClass::Sdxc,
+        }
+    }
+
+    /// The number of 512 byte data blocks in the user data area of the card.
+    pub fn block_count(&self) -> u32 {
+        (self.capacity() / BLOCK_SIZE as u64)
+            .try_into()
+            .unwrap_or(u32::MAX)
+    }
+
+    /// The programmable part of the CSD register.
+    pub fn programmable(&self) -> ProgrammableCsd {
+        ProgrammableCsd {
+            file_format_group: self.bits(15, 15) == 1,
+            copy: self.bits(14, 14) == 1,
+            permanent_write_protect: self.bits(13, 13) == 1,
+            temporary_write_protect: self.bits(12, 12) == 1,
+            file_format: self.bits(11, 10) as u8,
+        }
+    }
+
+    /// A copy of the CSD register with the programmable part replaced by
+    /// `programmable` and the CRC recalculated.
+    pub fn with_programmable(&self, programmable: ProgrammableCsd) -> Csd {
+        let mut bytes = self.0;
+
+        bytes[14] = (u8::from(programmable.file_format_group) << 7)
+            | (u8::from(programmable.copy) << 6)
+            | (u8::from(programmable.permanent_write_protect) << 5)
+            | (u8::from(programmable.temporary_write_protect) << 4)
+            | ((programmable.file_format & 0b11) << 2)
+            | (bytes[14] & 0b11);
+        bytes[15] = (crc7(&bytes[..15]) << 1) | 1;
+
+        Csd(bytes, self.1)
+    }
+
+    /// Whether the whole card is write protected, either temporarily or
+    /// permanently.
+    pub fn is_write_protected(&self) -> bool {
+        self.bits(13, 12) != 0
+    }
+
+    /// The raw bytes of the CSD register.
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+
+    /// The maximum data transfer rate of the card in Hz (TRAN_SPEED).
+    ///
+    /// This is the maximum SPI clock rate for the card. It is `None` if
+    /// TRAN_SPEED has a reserved value.
+    pub fn max_clock(&self) -> Option<u32> {
+        // The time values are multiplied by 10 (see Table 5-6). MMC cards
+        // use 2.6 and 5.2 where SD cards use 2.5 and 5.0.
+        const TIME_VALUES: [u32; 16] = [
+            0, 10, 12, 13, 15, 20, 25, 30, 35, 40, 45, 50, 55, 60, 70, 80,
+        ];
+        const MMC_TIME_VALUES: [u32; 16] = [
+            0, 10, 12, 13, 15, 20, 26, 30, 35, 40, 45, 52, 55, 60, 70, 80,
+        ];
+
+        let rate_unit = match self.bits(98, 96) {
+            0 => 100_000,
+            1 => 1_000_000,
+            2 => 10_000_000,
+            3 => 100_000_000,
+            _ => return None,
+        };
+
+        let time_values = match self.1 {
+            CardType::Sd => &TIME_VALUES,
+            CardType::Mmc => &MMC_TIME_VALUES,
+        };
+
+        match time_values[self.bits(102, 99) as usize] {
+            0 => None,
+            value => Some(rate_unit / 10 * value),
+        }
+    }
+
+    /// Whether the card supports the SwitchFunc command (command class 10 in
+    /// CCC).
+    ///
+    /// This is always `false` for an MMC card, where command class 10 is a
+    /// different switch command.
+    pub fn supports_switch_func(&self) -> bool {
+        self.1 == CardType::Sd && self.bits(94, 94) == 1
+    }
+
+    /// The number of 512 byte data blocks in a write protect group.
+    ///
+    /// This is `None` if the card does not support write protect groups,
+    /// which is always the case for a version 2.0 CSD (see section 5.3.3).
+    pub fn write_protect_group_size(&self) -> Option<u32> {
+        match self.version() {
+            CsdVersion::V1 if self.bits(31, 31) == 1 => {
+                let sector_size = self.bits(45, 39) + 1;
+                let wp_grp_size = self.bits(38, 32) + 1;
+                let write_bl_len = self.bits(25, 22);
+
+                Some(((sector_size * wp_grp_size) << write_bl_len) / BLOCK_SIZE as u32)
+            }
+            CsdVersion::Mmc if self.bits(31, 31) == 1 => {
+                let erase_grp_size = self.bits(46, 42) + 1;
+                let erase_grp_mult = self.bits(41, 37) + 1;
+                let wp_grp_size = self.bits(36, 32) + 1;
+                let write_bl_len = self.bits(25, 22);
+
+                Some(
+                    ((erase_grp_size * erase_grp_mult * wp_grp_size) << write_bl_len)
+                        / BLOCK_SIZE as u32,
+                )
+            }
+            _ => None,
+        }
+    }
+
+    /// The number of 512 byte data blocks in an erase group.
+    ///
+    /// This is `None` for an SD card, which can erase any range of blocks.
+    /// An MMC card only erases whole erase groups.
+    pub fn erase_group_size(&self) -> Option<u32> {
+        match self.version() {
+            CsdVersion::Mmc => {
+                let erase_grp_size = self.bits(46, 42) + 1;
+                let erase_grp_mult = self.bits(41, 37) + 1;
+                let write_bl_len = self.bits(25, 22);
+
+                Some(((erase_grp_size * erase_grp_mult) << write_bl_len) / BLOCK_SIZE as u32)
+            }
+            _ => None,
+        }
+    }
+
+    /// The time in microseconds that a card without an SD Status may take to
+    /// erase `blocks` blocks.
+    ///
+    /// This allows the default of 250 ms for each allocation unit (see
+    /// section 4.14 of the Simplified Specification), where an erase group
+    /// is taken to be the allocation unit of an MMC card.
+    pub(crate) fn erase_busy_timeout(&self, blocks: u32) -> u32 {
+        let group_size = u64::from(cmp::max(1, self.erase_group_size().unwrap_or(1)));
+        let blocks = u64::from(cmp::max(1, blocks));
+        let groups = blocks / group_size + u64::from(blocks % group_size != 0);
+
+        (DEFAULT_AU_ERASE_TIMEOUT * groups)
+            .try_into()
+            .unwrap_or(u32::MAX)
+    }
+
+    fn bits(&self, msb: usize, lsb: usize) -> u32 {
+        extract_bits(&self.0, msb, lsb)
+    }
+}
+
+impl Cid {
+    pub(crate) fn new(bytes: [u8; 16]) -> Result<Self, ResponseError> {
+        ensure!(crc7(&bytes[..15]) == bytes[15] >> 1, CidCrcMismatchSnafu);
+
+        Ok(Cid(bytes))
+    }
+
+    /// The manufacturer ID (MID) assigned by the SD-3C, LLC.
+    pub fn manufacturer_id(&self) -> u8 {
+        self.0[0]
+    }
+
+    /// The OEM/application ID (OID) assigned by the SD-3C, LLC.
+    ///
+    /// This is 2 ASCII characters. It is `None` if the card reports an ID
+    /// that is not ASCII.
+    pub fn oem_id(&self) -> Option<&str> {
+        ascii(&self.0[1..3])
+    }
+
+    /// The product name (PNM).
+    ///
+    /// This is 5 ASCII characters. It is `None` if the card reports a name
+    /// that is not ASCII.
+    pub fn product_name(&self) -> Option<&str> {
+        ascii(&self.0[3..8])
+    }
+
+    /// The product revision (PRV) as its major and minor revision numbers.
+    pub fn product_revision(&self) -> (u8, u8) {
+        (self.0[8] >> 4, self.0[8] & 0x0f)
+    }
+
+    /// The product serial number (PSN).
+    pub fn serial_number(&self) -> u32 {
+        u32::from_be_bytes([self.0[9], self.0[10], self.0[11], self.0[12]])
+    }
+
+    /// The manufacturing date (MDT) as its year and month.
+    ///
+    /// The month is 1 for January through 12 for December.
+    pub fn manufacturing_date(&self) -> (u16, u8) {
+        let year = extract_bits(&self.0, 19, 12) as u16 + 2000;
+        let month = extract_bits(&self.0, 11, 8) as u8;
+
+        (year, month)
+    }
+}
+
+impl Scr {
+    pub(crate) fn new(bytes: [u8; 8]) -> Result<Self, ResponseError> {
+        let scr = Scr(bytes);
+        ensure!(scr.bits(63, 60) == 0, UnsupportedScrStructureSnafu);
+
+        Ok(scr)
+    }
+
+    /// The version of the Physical Layer Specification that the card
+    /// supports as its major and minor version numbers.
+    ///
+    /// This is decoded from SD_SPEC, SD_SPEC3, SD_SPEC4 and SD_SPECX as in
+    /// Table 5-18 of the Simplified Specification. The minor version is 0
+    /// for the versions where the SCR only identifies the major version
+    /// (version 3.0X and later). This is `None` for a combination of values
+    /// that the Simplified Specification does not define.
+    pub fn spec_version(&self) -> Option<(u8, u8)> {
+        let sd_spec = self.bits(59, 56);
+        let sd_spec3 = self.bits(47, 47);
+        let sd_spec4 = self.bits(42, 42);
+        let sd_specx = self.bits(41, 38);
+
+        match (sd_spec, sd_spec3, sd_spec4, sd_specx) {
+            (0, 0, 0, 0) => Some((1, 0)),
+            (1, 0, 0, 0) => Some((1, 10)),
+            (2, 0, 0, 0) => Some((2, 0)),
+            (2, 1, 0, 0) => Some((3, 0)),
+            (2, 1, 1, 0) => Some((4, 0)),
+            (2, 1, _, 1..=5) => Some((sd_specx as u8 + 4, 0)),
+            _ => None,
+        }
+    }
+
+    /// The data status after an erase (DATA_STAT_AFTER_ERASE).
+    ///
+    /// This is `true` if erased data reads as all 1 bits and `false` if it
+    /// reads as all 0 bits.
+    pub fn data_stat_after_erase(&self) -> bool {
+        self.bits(55, 55) == 1
+    }
+
+    /// The security features that the card supports (SD_SECURITY).
+    pub fn security(&self) -> Security {
+        match self.bits(54, 52) {
+            0 => Security::None,
+            2 => Security::Version1,
+            3 => Security::Version2,
+            4 => Security::Version3,
+            other => Security::Reserved(other as u8),
+        }
+    }
+
+    /// Whether the card supports the speed class control command (CMD20).
+    pub fn supports_cmd20(&self) -> bool {
+        self.bits(32, 32) == 1
+    }
+
+    /// Whether the card supports the set block count command (CMD23).
+    pub fn supports_cmd23(&self) -> bool {
+        self.bits(33, 33) == 1
+    }
+
+    /// Whether the card supports the single block extension register
+    /// commands (CMD48 and CMD49).
+    pub fn supports_cmd48(&self) -> bool {
+        self.bits(34, 34) == 1
+    }
+
+    /// Whether the card supports the multiple block extension register
+    /// commands (CMD58 and CMD59).
+    ///
+    /// Note that in SPI mode CMD58 and CMD59 are ReadOCR and CRCOnOff
+    /// regardless of this value.
+    pub fn supports_cmd58(&self) -> bool {
+        self.bits(35, 35) == 1
+    }
+
+    fn bits(&self, msb: usize, lsb: usize) -> u32 {
+        extract_bits(&self.0, msb, lsb)
+    }
+}
+
+impl SdStatus {
+    pub(crate) fn new(bytes: [u8; 64]) -> Self {
+        SdStatus(bytes)
+    }
+
+    /// The speed class of the card (SPEED_CLASS).
+    ///
+    /// This is the class number (0, 2, 4, 6 or 10), or `None` for a reserved
+    /// value.
+    pub fn speed_class(&self) -> Option<u8> {
+        match self.bits(447, 440) {
+            0 => Some(0),
+            1 => Some(2),
+            2 => Some(4),
+            3 => Some(6),
+            4 => Some(10),
+            _ => None,
+        }
+    }
+
+    /// The UHS speed grade of the card (UHS_SPEED_GRADE).
+    ///
+    /// This is 0 for less than 10 MB/s, 1 for U1 (10 MB/s) or 3 for U3
+    /// (30 MB/s).
+    pub fn uhs_speed_grade(&self) -> u8 {
+        self.bits(399, 396) as u8
+    }
+
+    /// The video speed class of the card (VIDEO_SPEED_CLASS).
+    ///
+    /// This is the class number (0, 6, 10, 30, 60 or 90).
+    pub fn video_speed_class(&self) -> u8 {
+        self.bits(391, 384) as u8
+    }
+
+    /// The size in bytes of an allocation unit (AU_SIZE).
+    ///
+    /// This is `None` if the card does not define the AU size.
+    pub fn au_size(&self) -> Option<u32> {
+        const KIB: u32 = 1024;
+        const MIB: u32 = 1024 * KIB;
+
+        match self.bits(431, 428) {
+            0 => None,
+            n @ 1..=0xa => Some((8 * KIB) << n),
+            0xb => Some(12 * MIB),
+            0xc => Some(16 * MIB),
+            0xd => Some(24 * MIB),
+            0xe => Some(32 * MIB),
+            _ => Some(64 * MIB),
+        }
+    }
+
+    /// The number of allocation units that are erased at a time for the
+    /// erase timeout (ERASE_SIZE).
+    ///
+    /// This is `None` if the card does not support the erase timeout
+    /// calculation.
+    pub fn erase_size(&self) -> Option<u16> {
+        match self.bits(423, 408) {
+            0 => None,
+            n => Some(n as u16),
+        }
+    }
+
+    /// The timeout in seconds to erase [`erase_size()`](SdStatus::erase_size)
+    /// allocation units (ERASE_TIMEOUT).
+    ///
+    /// This is `None` if the card does not support the erase timeout
+    /// calculation.
+    pub fn erase_timeout(&self) -> Option<u8> {
+        match self.bits(407, 402) {
+            0 => None,
+            n => Some(n as u8),
+        }
+    }
+
+    /// The fixed offset in seconds that is added to the erase timeout
+    /// (ERASE_OFFSET).
+    pub fn erase_offset(&self) -> u8 {
+        self.bits(401, 400) as u8
+    }
+
+    /// Whether the card supports the discard erase (DISCARD_SUPPORT).
+    pub fn supports_discard(&self) -> bool {
+        self.bits(313, 313) == 1
+    }
+
+    /// Whether the card supports the full user area logical erase
+    /// (FULE_SUPPORT).
+    pub fn supports_fule(&self) -> bool {
+        self.bits(312, 312) == 1
+    }
+
+    /// The time in microseconds that the card may take to erase `blocks`
+    /// blocks.
+    ///
+    /// This follows the erase timeout calculation in section 4.14 of the
+    /// Simplified Specification. If the card does not support that
+    /// calculation then 250 ms is allowed for each allocation unit. If the
+    /// card does not define the AU size then each block is treated as its own
+    /// allocation unit.
+    pub(crate) fn erase_busy_timeout(&self, blocks: u32) -> u32 {
+        const MICROS_PER_SEC: u64 = 1_000_000;
+
+        let blocks = u64::from(cmp::max(1, blocks));
+        let au_count = match self.au_size() {
+            Some(au_size) => {
+                let au_blocks = u64::from(cmp::max(1, au_size / BLOCK_SIZE as u32));
+                blocks / au_blocks + u64::from(blocks % au_blocks != 0)
+            }
+            None => blocks,
+        };
+
+        let timeout = match (self.erase_size(), self.erase_timeout()) {
+            (Some(size), Some(timeout)) => {
+                u64::from(timeout) * MICROS_PER_SEC * au_count / u64::from(size)
+                    + u64::from(self.erase_offset()) * MICROS_PER_SEC
+            }
+            _ => DEFAULT_AU_ERASE_TIMEOUT * au_count,
+        };
+
+        timeout.try_into().unwrap_or(u32::MAX)
+    }
+
+    fn bits(&self, msb: usize, lsb: usize) -> u32 {
+        extract_bits(&self.0, msb, lsb)
+    }
+}
+
+impl SwitchStatus {
+    pub fn new(bytes: [u8; 64]) -> Self {
+        SwitchStatus(bytes)
+    }
+
+    /// Whether the card supports `function` in function group `group`.
+    ///
+    /// The function groups are numbered from 1 to 6.
+    pub fn supports_function(&self, group: usize, function: usize) -> bool {
+        debug_assert!((1..=6).contains(&group) && function < 16);
+
+        let lsb = 384 + 16 * group;
+        self.bits(lsb + function, lsb + function) == 1
+    }
+
+    /// The function that is (or would be) selected in function group
+    /// `group`.
+    ///
+    /// This is 0xF if the selected function could not be switched to.
+    pub fn selected_function(&self, group: usize) -> u8 {
+        debug_assert!((1..=6).contains(&group));
+
+        let lsb = 376 + 4 * (group - 1);
+        self.bits(lsb + 3, lsb) as u8
+    }
+
+    fn bits(&self, msb: usize, lsb: usize) -> u32 {
+        extract_bits(&self.0, msb, lsb)
+    }
+}
+
+fn ascii(bytes: &[u8]) -> Option<&str> {
+    if bytes.is_ascii() {
+        core::str::from_utf8(bytes).ok()
+    } else {
+        None
+    }
+}
+
+/// Extract the bits `msb` down to `lsb` (inclusive) from a register that is
+/// sent most significant byte first.
+///
+/// The bit numbering follows the register tables in the Simplified
+/// Specification, where bit 0 is the least significant bit of the last byte.
+fn extract_bits(bytes: &[u8], msb: usize, lsb: usize) -> u32 {
+    debug_assert!(msb >= lsb && msb - lsb < 32);
+
+    (lsb..=msb).rev().fold(0, |acc, bit| {
+        let byte = bytes[bytes.len() - 1 - bit / 8];
+        (acc << 1) | u32::from((byte >> (bit % 8)) & 1)
+    })
+}
+
 // This set of constants is desiged to be all of the specificed values, whether
 // they are used in this crate or not. This is taked from section 7.3.2.1 of
 // the Simplified Specification.
@@ -352,10 +1293,376 @@ mod tests {
         assert_eq!(r3.card_capacity(), CardCapacity::HighOrExtended);
     }
 
+    #[test]
+    fn extract_bits_crosses_byte_boundaries() {
+        let bytes = [0b0000_0011, 0b1100_0000, 0];
+
+        assert_eq!(extract_bits(&bytes, 17, 14), 0b1111);
+        assert_eq!(extract_bits(&bytes, 23, 16), 0b0000_0011);
+    }
+
+    #[test]
+    fn csd_v1_gives_expected_capacity() {
+        // C_SIZE = 4095, C_SIZE_MULT = 7, READ_BL_LEN = 10
+        let csd = Csd::new([
+            0x00, 0x00, 0x00, 0x32, 0x5f, 0x5a, 0x83, 0xff, 0xc0, 0x03, 0x80, 0x00, 0x00, 0x00,
+            0x00, 0x01,
+        ])
+        .expect("invalid CSD");
+
+        assert_eq!(csd.version(), CsdVersion::V1);
+        assert_eq!(csd.capacity_class(), CapacityClass::Sdsc);
+        assert_eq!(csd.capacity(), 2 * 1024 * 1024 * 1024);
+        assert_eq!(csd.write_protect_group_size(), None);
+    }
+
+    #[test]
+    fn csd_v1_with_wp_grp_enable_gives_expected_group_size() {
+        // SECTOR_SIZE = 127, WP_GRP_SIZE = 31, WP_GRP_ENABLE = 1,
+        // WRITE_BL_LEN = 9
+        let csd = Csd::new([
+            0x00, 0x00, 0x00, 0x32, 0x5f, 0x5a, 0x83, 0xff, 0xc0, 0x03, 0xbf, 0x9f, 0x82, 0x40,
+            0x00, 0x01,
+        ])
+        .expect("invalid CSD");
+
+        assert_eq!(csd.write_protect_group_size(), Some(128 * 32));
+    }
+
+    #[test]
+    fn csd_v2_gives_expected_capacity() {
+        // C_SIZE = 0x3b37
+        let csd = Csd::new([
+            0x40, 0x0e, 0x00, 0x32, 0x5b, 0x59, 0x00, 0x00, 0x3b, 0x37, 0x7f, 0x80, 0x0a, 0x40,
+            0x00, 0x01,
+        ])
+        .expect("invalid CSD");
+
+        assert_eq!(csd.version(), CsdVersion::V2);
+        assert_eq!(csd.capacity_class(), CapacityClass::Sdhc);
+        assert_eq!(csd.capacity(), 0x3b38 * 512 * 1024);
+        assert_eq!(csd.block_count(), 0x3b38 * 1024);
+        assert_eq!(csd.write_protect_group_size(), None);
+        assert_eq!(csd.erase_group_size(), None);
+        assert_eq!(csd.max_clock(), Some(25_000_000));
+    }
+
+    #[test]
+    fn csd_v2_above_32_gb_is_sdxc() {
+        // C_SIZE = 0x01dcd5 (64 GB)
+        let mut bytes = [0; 16];
+        bytes[0] = 0x40;
+        bytes[7] = 0x01;
+        bytes[8] = 0xdc;
+        bytes[9] = 0xd5;
+        let csd = Csd::new(bytes).expect("invalid CSD");
+
+        assert_eq!(csd.capacity_class(), CapacityClass::Sdxc);
+    }
+
+    #[test]
+    fn csd_max_clock_decodes_tran_speed() {
+        let mut bytes = [0; 16];
+
+        bytes[3] = 0x5a;
+        assert_eq!(Csd::new(bytes).unwrap().max_clock(), Some(50_000_000));
+        bytes[3] = 0x09;
+        assert_eq!(Csd::new(bytes).unwrap().max_clock(), Some(1_000_000));
+        bytes[3] = 0x02;
+        assert_eq!(Csd::new(bytes).unwrap().max_clock(), None);
+        bytes[3] = 0x0c;
+        assert_eq!(Csd::new(bytes).unwrap().max_clock(), None);
+    }
+
+    #[test]
+    fn csd_with_programmable_sets_bits_and_crc() {
+        let csd = Csd::new([
+            0x40, 0x0e, 0x00, 0x32, 0x5b, 0x59, 0x00, 0x00, 0x3b, 0x37, 0x7f, 0x80, 0x0a, 0x40,
+            0x00, 0x01,
+        ])
+        .expect("invalid CSD");
+        let programmable = ProgrammableCsd {
+            temporary_write_protect: true,
+            file_format: 2,
+            ..csd.programmable()
+        };
+
+        let programmed = csd.with_programmable(programmable);
+
+        assert_eq!(programmed.programmable(), programmable);
+        assert_eq!(programmed.as_bytes()[14], 0b0001_1000);
+        assert_eq!(
+            programmed.as_bytes()[15] >> 1,
+            crc7(&programmed.as_bytes()[..15])
+        );
+        assert!(programmed.is_write_protected());
+        assert!(!csd.is_write_protected());
+    }
+
+    #[test]
+    fn csd_v3_is_unsupported() {
+        let result = Csd::new([0x80; 16]);
+
+        assert_eq!(result, Err(ResponseError::UnsupportedCsdStructure));
+    }
+
+    #[test]
+    fn mmc_csd_decodes_fields() {
+        // CSD_STRUCTURE = 2, SPEC_VERS = 3, TRAN_SPEED = 26 MHz, C_SIZE =
+        // 4095, C_SIZE_MULT = 7, READ_BL_LEN = 10, ERASE_GRP_SIZE = 31,
+        // ERASE_GRP_MULT = 0, WP_GRP_SIZE = 0, WP_GRP_ENABLE = 1,
+        // WRITE_BL_LEN = 9
+        let csd = Csd::new_mmc([
+            0x8c, 0x00, 0x00, 0x32, 0x5f, 0x5a, 0x83, 0xff, 0xc0, 0x03, 0xfc, 0x00, 0x82, 0x40,
+            0x00, 0x01,
+        ])
+        .expect("invalid CSD");
+
+        assert_eq!(csd.version(), CsdVersion::Mmc);
+        assert_eq!(csd.mmc_spec_version(), Some(3));
+        assert_eq!(csd.capacity(), 2 * 1024 * 1024 * 1024);
+        assert_eq!(csd.max_clock(), Some(26_000_000));
+        assert_eq!(csd.write_protect_group_size(), Some(32));
+        assert_eq!(csd.erase_group_size(), Some(32));
+        assert!(!csd.supports_switch_func());
+    }
+
+    #[test]
+    fn mmc_csd_allows_default_erase_timeout_for_each_erase_group() {
+        // ERASE_GRP_SIZE = 31, ERASE_GRP_MULT = 0, WRITE_BL_LEN = 9
+        let csd = Csd::new_mmc([
+            0x8c, 0x00, 0x00, 0x32, 0x5f, 0x5a, 0x83, 0xff, 0xc0, 0x03, 0xfc, 0x00, 0x82, 0x40,
+            0x00, 0x01,
+        ])
+        .expect("invalid CSD");
+
+        assert_eq!(csd.erase_busy_timeout(32), 250_000);
+        assert_eq!(csd.erase_busy_timeout(33), 500_000);
+        assert_eq!(csd.erase_busy_timeout(u32::MAX), u32::MAX);
+    }
+
+    #[test]
+    fn sd_csd_has_no_mmc_spec_version() {
+        let csd = Csd::new([0; 16]).expect("invalid CSD");
+
+        assert_eq!(csd.mmc_spec_version(), None);
+    }
+
+    #[test]
+    fn mmc_csd_with_ext_csd_structure_is_unsupported() {
+        let result = Csd::new_mmc([0xc0; 16]);
+
+        assert_eq!(result, Err(ResponseError::UnsupportedCsdStructure));
+    }
+
+    #[test]
+    fn cid_decodes_fields() {
+        let cid = Cid::new([
+            0x03, 0x53, 0x44, 0x53, 0x43, 0x31, 0x36, 0x47, 0x80, 0x12, 0x34, 0x56, 0x78, 0x01,
+            0x48, 0x9d,
+        ])
+        .expect("invalid CID");
+
+        assert_eq!(cid.manufacturer_id(), 0x03);
+        assert_eq!(cid.oem_id(), Some("SD"));
+        assert_eq!(cid.product_name(), Some("SC16G"));
+        assert_eq!(cid.product_revision(), (8, 0));
+        assert_eq!(cid.serial_number(), 0x1234_5678);
+        assert_eq!(cid.manufacturing_date(), (2020, 8));
+    }
+
+    #[test]
+    fn cid_with_crc_mismatch_is_error() {
+        let result = Cid::new([
+            0x03, 0x53, 0x44, 0x53, 0x43, 0x31, 0x36, 0x47, 0x80, 0x12, 0x34, 0x56, 0x78, 0x01,
+            0x48, 0x01,
+        ]);
+
+        assert_eq!(result, Err(ResponseError::CidCrcMismatch));
+    }
+
+    #[test]
+    fn scr_decodes_fields() {
+        let scr = Scr::new([0x02, 0xb5, 0x80, 0x03, 0, 0, 0, 0]).expect("invalid SCR");
+
+        assert_eq!(scr.spec_version(), Some((3, 0)));
+        assert!(scr.data_stat_after_erase());
+        assert_eq!(scr.security(), Security::Version2);
+        assert!(scr.supports_cmd20());
+        assert!(scr.supports_cmd23());
+        assert!(!scr.supports_cmd48());
+        assert!(!scr.supports_cmd58());
+    }
+
+    #[test]
+    fn scr_with_sd_specx_gives_later_spec_version() {
+        // SD_SPEC = 2, SD_SPEC3 = 1, SD_SPEC4 = 1, SD_SPECX = 2
+        let scr = Scr::new([0x02, 0x45, 0x84, 0x80, 0, 0, 0, 0]).expect("invalid SCR");
+
+        assert_eq!(scr.spec_version(), Some((6, 0)));
+        assert_eq!(scr.security(), Security::Version3);
+    }
+
+    #[test]
+    fn scr_with_unknown_structure_is_error() {
+        let result = Scr::new([0x12, 0, 0, 0, 0, 0, 0, 0]);
+
+        assert_eq!(result, Err(ResponseError::UnsupportedScrStructure));
+    }
+
+    #[test]
+    fn r2_with_no_status_bits_is_ok() {
+        let r2 = R2Response::create(R1Response(0), &[0]);
+
+        assert_eq!(r2.check_error(), Ok(()));
+    }
+
+    #[test]
+    fn r2_with_wp_violation_is_error() {
+        let r2 = R2Response::create(R1Response(0), &[0b0010_0000]);
+
+        assert_eq!(r2.check_error(), Err(CardStatusError::WpViolation));
+    }
+
+    #[test]
+    fn r2_without_lock_ignores_card_locked() {
+        let r2 = R2Response::create(R1Response(0), &[0b0000_0001]);
+
+        assert!(r2.is_locked());
+        assert_eq!(r2.check_error(), Err(CardStatusError::CardLocked));
+        assert_eq!(r2.without_lock().check_error(), Ok(()));
+    }
+
+    #[test]
+    fn r2_multi_error_is_lowest_bit() {
+        let r2 = R2Response::create(R1Response(0), &[0b1001_0000]);
+
+        assert_eq!(r2.check_error(), Err(CardStatusError::CardEccFailed));
+    }
+
+    #[test]
+    fn sd_status_decodes_fields() {
+        let mut bytes = [0; 64];
+        bytes[8] = 0x04; // SPEED_CLASS: class 10
+        bytes[10] = 0x90; // AU_SIZE: 4 MiB
+        bytes[11] = 0x00; // ERASE_SIZE
+        bytes[12] = 0x08; // ERASE_SIZE
+        bytes[13] = 0b0000_1001; // ERASE_TIMEOUT: 2 s, ERASE_OFFSET: 1 s
+        bytes[14] = 0x30; // UHS_SPEED_GRADE: U3
+        bytes[15] = 30; // VIDEO_SPEED_CLASS: V30
+        bytes[24] = 0b0000_0011; // DISCARD_SUPPORT and FULE_SUPPORT
+        let status = SdStatus::new(bytes);
+
+        assert_eq!(status.speed_class(), Some(10));
+        assert_eq!(status.au_size(), Some(4 * 1024 * 1024));
+        assert_eq!(status.erase_size(), Some(8));
+        assert_eq!(status.erase_timeout(), Some(2));
+        assert_eq!(status.erase_offset(), 1);
+        assert_eq!(status.uhs_speed_grade(), 3);
+        assert_eq!(status.video_speed_class(), 30);
+        assert!(status.supports_discard());
+        assert!(status.supports_fule());
+    }
+
+    #[test]
+    fn sd_status_without_erase_timing_is_none() {
+        let status = SdStatus::new([0; 64]);
+
+        assert_eq!(status.au_size(), None);
+        assert_eq!(status.erase_size(), None);
+        assert_eq!(status.erase_timeout(), None);
+    }
+
+    #[test]
+    fn sd_status_erase_busy_timeout_uses_erase_timing() {
+        let mut bytes = [0; 64];
+        bytes[10] = 0x90; // AU_SIZE: 4 MiB
+        bytes[12] = 0x08; // ERASE_SIZE: 8 AUs
+        bytes[13] = 0x09; // ERASE_TIMEOUT: 2 seconds, ERASE_OFFSET: 1 second
+        let status = SdStatus::new(bytes);
+
+        // 16 MiB is 4 AUs which takes 4 * 2 / 8 + 1 seconds
+        assert_eq!(status.erase_busy_timeout(0x8000), 2_000_000);
+    }
+
+    #[test]
+    fn sd_status_erase_busy_timeout_without_erase_timing_is_per_au() {
+        let mut bytes = [0; 64];
+        bytes[10] = 0x90; // AU_SIZE: 4 MiB
+        let status = SdStatus::new(bytes);
+
+        // A partial AU counts as a whole AU
+        assert_eq!(status.erase_busy_timeout(0x2001), 500_000);
+    }
+
+    #[test]
+    fn switch_status_decodes_group_1() {
+        let mut bytes = [0; 64];
+        bytes[13] = 0b0000_0011; // group 1 supports functions 0 and 1
+        bytes[16] = 0xf1; // group 2 can't switch, group 1 selects function 1
+        let status = SwitchStatus::new(bytes);
+
+        assert!(status.supports_function(1, 0));
+        assert!(status.supports_function(1, 1));
+        assert!(!status.supports_function(1, 2));
+        assert_eq!(status.selected_function(1), 1);
+        assert_eq!(status.selected_function(2), 0xf);
+    }
+
     #[test]
     fn r3_with_css_unset_gives_expected_capacity() {
         let r3 = R3Response::new(0, 0, 0, 0, R1Response(0));
 
         assert_eq!(r3.card_capacity(), CardCapacity::Standard);
     }
+
+    #[test]
+    fn r4_for_sdio_card_has_functions_and_no_memory() {
+        let r4 = R4Response::new(0b1010_0000, 0xff, 0x80, 0, R1Response(0));
+
+        assert_eq!(r4.io_functions(), 2);
+        assert!(!r4.memory_present());
+    }
+
+    #[test]
+    fn r4_for_combo_card_has_memory() {
+        let r4 = R4Response::new(0b1001_1000, 0xff, 0x80, 0, R1Response(0));
+
+        assert_eq!(r4.io_functions(), 1);
+        assert!(r4.memory_present());
+    }
+
+    #[test]
+    fn r3_voltage_window_is_from_ocr() {
+        // 3.2 V to 3.4 V (bits 20 and 21)
+        let r3 = R3Response::new(0x80, 0b0011_0000, 0, 0, R1Response(0));
+        let window = r3.voltage_window();
+
+        assert_eq!(window.min_millivolts(), Some(3_200));
+        assert_eq!(window.max_millivolts(), Some(3_400));
+        assert!(window.contains(3_300));
+        assert!(!window.contains(3_500));
+        assert!(!window.contains(3_000));
+    }
+
+    #[test]
+    fn full_voltage_window_contains_whole_range() {
+        let r3 = R3Response::new(0, 0xff, 0x80, 0, R1Response(0));
+        let window = r3.voltage_window();
+
+        assert!(window.contains(2_700));
+        assert!(window.contains(3_600));
+        assert!(!window.contains(1_800));
+        assert_eq!(window.max_millivolts(), Some(3_600));
+    }
+
+    #[test]
+    fn empty_voltage_window_has_no_limits() {
+        let r3 = R3Response::new(0, 0, 0, 0, R1Response(0));
+        let window = r3.voltage_window();
+
+        assert!(!window.contains(3_300));
+        assert_eq!(window.min_millivolts(), None);
+        assert_eq!(window.max_millivolts(), None);
+    }
 }
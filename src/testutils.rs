@@ -8,12 +8,19 @@
 
 //! Utilities to support tests.
 
+use std::collections::VecDeque;
+
 use embedded_hal::{
     blocking::spi::{Transfer, Write},
     digital::v2::OutputPin,
 };
+use embedded_hal_mock::delay;
 
-use crate::common;
+use crate::{
+    common::{self, BLOCK_SIZE},
+    timer::{DelayTimer, Timer},
+    tokens,
+};
 
 #[derive(Debug)]
 pub struct StubSpi;
@@ -49,99 +56,252 @@ impl Transfer<u8> for StubSpi {
     }
 }
 
+impl DelayTimer for delay::MockNoop {
+    fn now_us(&mut self) -> Option<u32> {
+        None
+    }
+}
+
+/// A fake timer that advances by a fixed step each time it is read.
+#[derive(Debug, Default)]
+pub struct StepTimer {
+    now: u32,
+    step: u32,
+}
+
+impl StepTimer {
+    /// A fake timer that starts at `start` and advances by `step`
+    /// microseconds.
+    pub fn new(start: u32, step: u32) -> Self {
+        StepTimer { now: start, step }
+    }
+}
+
+impl Timer for StepTimer {
+    fn now_us(&mut self) -> u32 {
+        let now = self.now;
+        self.now = self.now.wrapping_add(self.step);
+        now
+    }
+}
+
+/// The CSD register of the fake card (an SDHC card with 7.4 GiB of capacity).
+pub const FAKE_CSD: [u8; 16] = [
+    0x40, 0x0e, 0x00, 0x32, 0x5b, 0x59, 0x00, 0x00, 0x3b, 0x37, 0x7f, 0x80, 0x0a, 0x40, 0x00, 0x01,
+];
+
+/// The CSD register of the fake card after it has switched to High Speed
+/// mode (TRAN_SPEED is 50 MHz).
+pub const FAKE_HIGH_SPEED_CSD: [u8; 16] = [
+    0x40, 0x0e, 0x00, 0x5a, 0x5b, 0x59, 0x00, 0x00, 0x3b, 0x37, 0x7f, 0x80, 0x0a, 0x40, 0x00, 0x01,
+];
+
+/// The CSD register of the fake MMC card (2 GiB of capacity and compliant
+/// with version 3 of the MultiMediaCard System Specification).
+pub const FAKE_MMC_CSD: [u8; 16] = [
+    0x8c, 0x00, 0x00, 0x32, 0x5f, 0x5a, 0x83, 0xff, 0xc0, 0x03, 0xfc, 0x00, 0x82, 0x40, 0x00, 0x01,
+];
+
+/// The CID register of the fake card.
+pub const FAKE_CID: [u8; 16] = [
+    0x03, 0x53, 0x44, 0x53, 0x43, 0x31, 0x36, 0x47, 0x80, 0x12, 0x34, 0x56, 0x78, 0x01, 0x48, 0x9d,
+];
+
+/// The SCR register of the fake card.
+pub const FAKE_SCR: [u8; 8] = [0x02, 0x35, 0x80, 0x03, 0x00, 0x00, 0x00, 0x00];
+
+/// The SD Status of the fake card (speed class 10 with 4 MiB AUs and discard
+/// support).
+pub const FAKE_SD_STATUS: [u8; 64] = [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x00, 0x90, 0x00, 0x08, 0x09, 0x10, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// The switch function status of the fake card (High Speed is supported and
+/// selected).
+pub const FAKE_SWITCH_STATUS: [u8; 64] = {
+    let mut status = [0; 64];
+    status[13] = 0b0000_0011;
+    status[16] = 0x01;
+    status
+};
+
+/// The kind of card that a [`FakeCard`] pretends to be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FakeCardType {
+    /// An SD memory card.
+    Sd,
+
+    /// An MMC card that rejects the SendIfCond command and every app
+    /// command.
+    Mmc,
+
+    /// An SDIO card with one I/O function and no memory portion.
+    Sdio,
+
+    /// A combo card with one I/O function and a memory portion.
+    Combo,
+}
+
+// Deriving Default for an enum needs the `#[default]` attribute from Rust 1.62.
+#[allow(clippy::derivable_impls)]
+impl Default for FakeCardType {
+    fn default() -> Self {
+        FakeCardType::Sd
+    }
+}
+
+/// A fake SD card that responds to every command with a non-idle, non-error
+/// response.
+///
+/// The bytes for the response to a command are queued when the command is
+/// written and are then returned by later transfers. A transfer with no
+/// queued bytes returns 0xff (the card is ready).
 #[derive(Debug, Default)]
 pub struct FakeCard {
-    state: State,
+    pending: VecDeque<u8>,
+    app_cmd: bool,
+    locked: bool,
+    high_speed: bool,
+    clock_rate: Option<u32>,
+    noisy_above: Option<u32>,
+    card_type: FakeCardType,
 }
 
-impl Write<u8> for FakeCard {
-    type Error = StubError;
+/// A builder for a [`FakeCard`] that differs from the default fake SD card.
+#[derive(Debug, Default)]
+pub struct FakeCardBuilder {
+    card: FakeCard,
+}
 
-    fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
-        match self.state {
-            State::Start => Ok(()),
-            State::CommandPending if words[0] & 0b1100_0000 == 0b0100_0000 => {
-                if words[0] & 0b0011_1111 == 8 {
-                    self.state = State::R7ResponsePending(4);
-                } else if words[0] & 0b0011_1111 == 58 {
-                    self.state = State::R3ResponsePending(4);
-                } else {
-                    self.state = State::ResponsePending;
-                }
-                Ok(())
-            }
-            State::CommandPending => todo!(),
-            State::ResponsePending => todo!(),
-            State::R3ResponsePending(_) => todo!(),
-            State::R7ResponsePending(_) => todo!(),
-        }
+impl FakeCardBuilder {
+    /// Set the kind of card that the fake card pretends to be.
+    pub fn card_type(mut self, card_type: FakeCardType) -> Self {
+        self.card.card_type = card_type;
+        self
+    }
+
+    /// Set whether the fake card is locked by a password until it receives
+    /// a LockUnlock command.
+    pub fn locked(mut self, locked: bool) -> Self {
+        self.card.locked = locked;
+        self
+    }
+
+    /// Make the fake card send data blocks with a bad CRC while its clock
+    /// rate is faster than `clock_rate`.
+    pub fn noisy_above(mut self, clock_rate: u32) -> Self {
+        self.card.noisy_above = Some(clock_rate);
+        self
+    }
+
+    /// Build the fake card.
+    pub fn build(self) -> FakeCard {
+        self.card
     }
 }
 
-impl Transfer<u8> for FakeCard {
-    type Error = StubError;
+impl FakeCard {
+    /// A builder for a fake card that starts out as the default fake SD
+    /// card.
+    pub fn builder() -> FakeCardBuilder {
+        FakeCardBuilder::default()
+    }
 
-    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Self::Error> {
-        match self.state {
-            State::Start if words[0] == 0xff => {
-                self.state = State::CommandPending;
-                Ok(words)
-            }
-            State::Start => Err(StubError),
-            State::CommandPending => todo!(),
-            State::ResponsePending => {
-                self.state = State::Start;
-                // Note: this is a non-idle, non-error R1 response
-                words[0] = 0;
-                Ok(words)
+    /// Set the clock rate of the fake card.
+    pub fn set_clock_rate(&mut self, clock_rate: u32) {
+        self.clock_rate = Some(clock_rate);
+    }
+
+    /// The clock rate that was last set on the fake card.
+    pub fn clock_rate(&self) -> Option<u32> {
+        self.clock_rate
+    }
+
+    fn queue_response(&mut self, cmd: u8, arg: u32) {
+        let app_cmd = self.app_cmd;
+        self.app_cmd = cmd == 55;
+
+        let mmc = self.card_type == FakeCardType::Mmc;
+
+        // Note: each of the responses starts with a non-idle, non-error R1
+        match cmd {
+            8 | 55 if mmc => self.pending.push_back(0b0000_0100), // illegal command
+            5 => match self.card_type {
+                FakeCardType::Sdio => self.pending.extend([0, 0b1001_0000, 0xff, 0x80, 0x00]),
+                FakeCardType::Combo => self.pending.extend([0, 0b1001_1000, 0xff, 0x80, 0x00]),
+                _ => self.pending.push_back(0b0000_0100), // illegal command
+            },
+            6 => {
+                self.high_speed |= arg & 0x8000_0000 != 0;
+                self.queue_data(&FAKE_SWITCH_STATUS);
             }
-            State::R3ResponsePending(byte) => {
-                self.state = if byte == 0 {
-                    State::Start
-                } else {
-                    State::R3ResponsePending(byte - 1)
-                };
-                words[0] = match byte {
-                    4 => 0,
-                    3 => 0,
-                    2 => 0,
-                    1 => 0,
-                    0 => 0,
-                    _ => panic!("unexpect byte count for R3 response"),
-                };
-                Ok(words)
+            8 => self.pending.extend([
+                0,
+                0,
+                0,
+                common::VOLTAGE_2_7_TO_3_6,
+                common::IF_COND_CHECK_PATTERN,
+            ]),
+            9 if mmc => self.queue_data(&FAKE_MMC_CSD),
+            9 if self.high_speed => self.queue_data(&FAKE_HIGH_SPEED_CSD),
+            9 => self.queue_data(&FAKE_CSD),
+            10 => self.queue_data(&FAKE_CID),
+            13 if app_cmd => {
+                self.pending.push_back(0);
+                self.queue_data(&FAKE_SD_STATUS);
             }
-            State::R7ResponsePending(byte) => {
-                self.state = if byte == 0 {
-                    State::Start
-                } else {
-                    State::R7ResponsePending(byte - 1)
-                };
-                words[0] = match byte {
-                    4 => 0,
-                    3 => 0,
-                    2 => 0,
-                    1 => common::VOLTAGE_2_7_TO_3_6,
-                    0 => common::IF_COND_CHECK_PATTERN,
-                    _ => panic!("unexpect byte count for R7 response"),
-                };
-                Ok(words)
+            13 => self.pending.extend([0, self.locked as u8]),
+            // The data response token (data accepted) follows the data block
+            // that the host writes after the R1 response.
+            17 => self.queue_data(&[0; BLOCK_SIZE]),
+            27 => self.pending.extend([0, 0b0000_0101]),
+            42 => {
+                self.locked = false;
+                self.pending.extend([0, 0b0000_0101]);
             }
+            51 if app_cmd => self.queue_data(&FAKE_SCR),
+            58 => self.pending.extend([0, 0x80, 0xff, 0x80, 0x00]),
+            _ => self.pending.push_back(0),
         }
     }
+
+    fn queue_data(&mut self, data: &[u8]) {
+        let noisy = matches!(
+            (self.clock_rate, self.noisy_above),
+            (Some(clock_rate), Some(limit)) if clock_rate > limit
+        );
+        let crc = tokens::crc16(data) ^ if noisy { 0xffff } else { 0 };
+
+        self.pending.extend([0, 0xff, tokens::START_BLOCK]);
+        self.pending.extend(data);
+        self.pending.extend(crc.to_be_bytes());
+    }
 }
 
-#[derive(Debug)]
-enum State {
-    Start,
-    CommandPending,
-    ResponsePending,
-    R3ResponsePending(u8),
-    R7ResponsePending(u8),
+impl Write<u8> for FakeCard {
+    type Error = StubError;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        if words.len() == 6 && words[0] & 0b1100_0000 == 0b0100_0000 {
+            let arg = u32::from_be_bytes([words[1], words[2], words[3], words[4]]);
+            self.queue_response(words[0] & 0b0011_1111, arg);
+        }
+
+        Ok(())
+    }
 }
 
-impl Default for State {
-    fn default() -> Self {
-        State::Start
+impl Transfer<u8> for FakeCard {
+    type Error = StubError;
+
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Self::Error> {
+        for word in words.iter_mut() {
+            *word = self.pending.pop_front().unwrap_or(0xff);
+        }
+
+        Ok(words)
     }
 }
@@ -0,0 +1,69 @@
+// Copyright 2022 Steven Bosnick
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE-2.0 or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms
+
+//! Types to support timeouts that are measured with a monotonic timer.
+
+use embedded_hal::blocking::delay::DelayUs;
+
+/// A monotonic timer with microsecond resolution.
+///
+/// The timer is used to measure the timeouts while waiting for the card (see
+/// [`SDCardConfig::timer`](crate::SDCardConfig::timer)).
+pub trait Timer {
+    /// The current time in microseconds since an arbitrary starting point.
+    ///
+    /// The time must never go backwards, except that it may wrap around from
+    /// `u32::MAX` to 0.
+    fn now_us(&mut self) -> u32;
+}
+
+/// The timer type of an [`SDCard`](crate::SDCard) that does not have a
+/// timer.
+///
+/// Without a timer the timeouts are measured by adding up the delays between
+/// the checks of the card, so the actual time before a timeout also includes
+/// the time spent on the SPI bus.
+#[derive(Debug)]
+pub enum NoTimer {}
+
+impl Timer for NoTimer {
+    fn now_us(&mut self) -> u32 {
+        match *self {}
+    }
+}
+
+/// A delay that may also have a timer to measure timeouts with.
+pub trait DelayTimer: DelayUs<u16> {
+    /// The current time in microseconds, or `None` if there is no timer.
+    fn now_us(&mut self) -> Option<u32>;
+}
+
+/// A delay together with an optional timer.
+pub struct TimedDelay<DELAY, TIMER> {
+    pub delay: DELAY,
+    pub timer: Option<TIMER>,
+}
+
+impl<DELAY, TIMER> DelayUs<u16> for TimedDelay<DELAY, TIMER>
+where
+    DELAY: DelayUs<u16>,
+{
+    fn delay_us(&mut self, us: u16) {
+        self.delay.delay_us(us)
+    }
+}
+
+impl<DELAY, TIMER> DelayTimer for TimedDelay<DELAY, TIMER>
+where
+    DELAY: DelayUs<u16>,
+    TIMER: Timer,
+{
+    fn now_us(&mut self) -> Option<u32> {
+        self.timer.as_mut().map(|timer| timer.now_us())
+    }
+}
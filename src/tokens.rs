@@ -0,0 +1,183 @@
+// Copyright 2022 Steven Bosnick
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE-2.0 or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms
+
+//! Types to support SD Card SPI Mode data tokens.
+//!
+//! Data is transfered to and from the card in data blocks which start with
+//! a start block token and end with a 16 bit CRC. If a read operation fails
+//! the card sends a data error token instead of the start block token. Every
+//! data block that is written to the card is answered with a data response
+//! token.
+//!
+//! This is based on section 7.3.3 of the Simplified Specification.
+
+use crc::{Crc, CRC_16_XMODEM};
+use snafu::{ensure, Snafu};
+
+/// The start block token for single block read, single block write and
+/// multiple block read (see section 7.3.3.2 of the Simplified Specification).
+pub const START_BLOCK: u8 = 0b1111_1110;
+
+/// The start block token for multiple block write (see section 7.3.3.2 of the
+/// Simplified Specification).
+pub const START_MULTIPLE_BLOCK_WRITE: u8 = 0b1111_1100;
+
+/// The stop tran token that ends a multiple block write (see section 7.3.3.2
+/// of the Simplified Specification).
+pub const STOP_TRAN: u8 = 0b1111_1101;
+
+static CRC16: Crc<u16> = Crc::<u16>::new(&CRC_16_XMODEM);
+
+/// Calculate the 16 bit CRC that follows the data in a data block.
+pub fn crc16(bytes: &[u8]) -> u16 {
+    CRC16.checksum(bytes)
+}
+
+/// Newtype to support decoding of a data response token.
+///
+/// This type is based on section 7.3.3.1 of the Simplified Specification.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DataResponseToken(u8);
+
+/// Newtype to support decoding of a data error token.
+///
+/// This type is based on section 7.3.3.3 of the Simplified Specification.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DataErrorToken(u8);
+
+#[derive(Debug, PartialEq, Snafu)]
+pub enum TokenError {
+    #[snafu(display("SD Card reported a general or unknown error."))]
+    GeneralError,
+
+    #[snafu(display("SD Card reported an internal card controller error."))]
+    CcError,
+
+    #[snafu(display("SD Card reported an ECC failure."))]
+    CardEccFailed,
+
+    #[snafu(display("SD Card reported an out of range argument."))]
+    OutOfRange,
+
+    #[snafu(display("SD Card rejected the data due to a CRC error."))]
+    DataCrcError,
+
+    #[snafu(display("SD Card rejected the data due to a write error."))]
+    WriteError,
+
+    #[snafu(display("SD Card sent an invalid data response token."))]
+    InvalidDataResponse,
+}
+
+impl DataResponseToken {
+    const MASK: u8 = 0b0001_1111;
+    const ACCEPTED: u8 = 0b0000_0101;
+    const CRC_ERROR: u8 = 0b0000_1011;
+    const WRITE_ERROR: u8 = 0b0000_1101;
+
+    pub fn new(value: u8) -> Self {
+        Self(value)
+    }
+
+    pub fn check_error(self) -> Result<(), TokenError> {
+        match self.0 & Self::MASK {
+            Self::ACCEPTED => Ok(()),
+            Self::CRC_ERROR => DataCrcSnafu.fail(),
+            Self::WRITE_ERROR => WriteSnafu.fail(),
+            _ => InvalidDataResponseSnafu.fail(),
+        }
+    }
+}
+
+impl DataErrorToken {
+    const ERROR: u8 = 0b0000_0001;
+    const CC_ERROR: u8 = 0b0000_0010;
+    const CARD_ECC_FAILED: u8 = 0b0000_0100;
+    const OUT_OF_RANGE: u8 = 0b0000_1000;
+
+    /// Decode `value` as a data error token if it is one.
+    ///
+    /// A data error token has the 4 high bits clear and at least one of the
+    /// error bits set. Any other value that the card sends while we are
+    /// waiting for a start block token is not a data error token.
+    pub fn from_byte(value: u8) -> Option<Self> {
+        if value & 0b1111_0000 == 0 && value != 0 {
+            Some(Self(value))
+        } else {
+            None
+        }
+    }
+
+    pub fn check_error(self) -> Result<(), TokenError> {
+        ensure!(self.0 & Self::ERROR == 0, GeneralSnafu);
+        ensure!(self.0 & Self::CC_ERROR == 0, CcSnafu);
+        ensure!(self.0 & Self::CARD_ECC_FAILED == 0, CardEccFailedSnafu);
+        ensure!(self.0 & Self::OUT_OF_RANGE == 0, OutOfRangeSnafu);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc16_matches_specification_example() {
+        // This is the example from section 4.5 of the Simplified
+        // Specification (512 bytes of 0xff).
+        assert_eq!(crc16(&[0xff; 512]), 0x7fa1);
+    }
+
+    #[test]
+    fn data_response_token_accepted_is_ok() {
+        let token = DataResponseToken::new(0b1110_0101);
+
+        assert_eq!(token.check_error(), Ok(()));
+    }
+
+    #[test]
+    fn data_response_token_crc_error_is_error() {
+        let token = DataResponseToken::new(0b0000_1011);
+
+        assert_eq!(token.check_error(), Err(TokenError::DataCrcError));
+    }
+
+    #[test]
+    fn data_response_token_write_error_is_error() {
+        let token = DataResponseToken::new(0b0000_1101);
+
+        assert_eq!(token.check_error(), Err(TokenError::WriteError));
+    }
+
+    #[test]
+    fn data_response_token_without_start_bits_is_invalid() {
+        let token = DataResponseToken::new(0xff);
+
+        assert_eq!(token.check_error(), Err(TokenError::InvalidDataResponse));
+    }
+
+    #[test]
+    fn start_block_is_not_data_error_token() {
+        assert_eq!(DataErrorToken::from_byte(START_BLOCK), None);
+    }
+
+    #[test]
+    fn data_error_token_with_out_of_range_is_error() {
+        let token = DataErrorToken::from_byte(0b0000_1000).expect("not a data error token");
+
+        assert_eq!(token.check_error(), Err(TokenError::OutOfRange));
+    }
+
+    #[test]
+    fn data_error_token_multi_error_is_lowest_bit() {
+        let token = DataErrorToken::from_byte(0b0000_0110).expect("not a data error token");
+
+        assert_eq!(token.check_error(), Err(TokenError::CcError));
+    }
+}
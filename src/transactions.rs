@@ -11,6 +11,8 @@
 //! The transactions include both those related to initilization and those
 //! related to data transfer (after initilization).
 
+use core::{cmp, iter};
+
 use embedded_hal::{
     blocking::{
         delay::DelayUs,
@@ -21,17 +23,118 @@ use embedded_hal::{
 use snafu::prelude::*;
 
 use crate::{
-    cmds::{self, HostCapacitySupport},
-    common::{self, CardCapacity},
-    resp::{R1Response, R3Response, R7Response, Response, ResponseError},
+    cmds::{self, CrcOption, EraseKind, HostCapacitySupport, LockUnlockOp, SwitchFuncMode},
+    common::{self, CardCapacity, CardType, UnsupportedCardType, Version, BLOCK_SIZE},
+    resp::{
+        CardStatusError, Cid, Csd, R1Response, R1bResponse, R2Response, R3Response, R4Response,
+        R7Response, Response, ResponseError, Scr, SdStatus, SwitchStatus, VoltageWindow,
+    },
+    timer::DelayTimer,
+    tokens::{self, DataErrorToken, DataResponseToken, TokenError},
 };
 
-const WAIT_FOR_CARD_COUNT: u32 = 32;
+const WAIT_FOR_CARD_TIMEOUT: u32 = 3_000;
 const WAIT_FOR_CARD_DELAY: u16 = 10;
 const MAX_WAIT_FOR_RESPONSE: u32 = 8;
 const MAX_IF_COND_COUNT: u32 = 5;
-const MAX_OP_COND_COUNT: u32 = 3_200;
 const OP_COND_DELAY: u16 = 50;
+const WAIT_FOR_DATA_DELAY: u16 = 100;
+const WAIT_WHILE_BUSY_DELAY: u16 = 100;
+const FORCE_ERASE_TIMEOUT: u32 = 180_000_000;
+
+/// The default time (in microseconds) to wait for the card to stop signaling
+/// busy.
+pub const DEFAULT_BUSY_TIMEOUT: u32 = 500_000;
+
+/// The time (in microseconds) that the card is allowed to finish its
+/// initilization after the first SdSendOpCond (see section 4.2.3 of the
+/// Simplified Specification).
+pub const OP_COND_TIMEOUT: u32 = 1_000_000;
+
+/// The time (in microseconds) that the card is allowed to send a data block
+/// after a read command (see section 4.6.2.1 of the Simplified
+/// Specification).
+pub const READ_TIMEOUT: u32 = 100_000;
+
+/// The time (in microseconds) to receive a byte at 100 kHz, the slowest
+/// clock rate for the `SPI` interface.
+///
+/// Without a timer this is the estimated time for each byte that is received
+/// while waiting for a response.
+const RESPONSE_BYTE_TIME: u32 = 80;
+
+/// The time (in microseconds) that the card is allowed to respond to a
+/// command: 8 bytes (N_CR, see section 7.5.4 of the Simplified
+/// Specification) at the slowest clock rate.
+const RESPONSE_TIMEOUT: u32 = MAX_WAIT_FOR_RESPONSE * RESPONSE_BYTE_TIME;
+
+/// The default supply voltage (in mV) that the host provides to the card.
+pub const DEFAULT_HOST_VOLTAGE: u16 = 3_300;
+
+/// How long to wait for the card to be ready for a command and then for the
+/// card to respond to the command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandWaits {
+    /// The time (in microseconds) to wait for the card to be ready.
+    pub ready_timeout: u32,
+
+    /// The delay (in microseconds) between the checks for whether the card
+    /// is ready.
+    pub ready_delay: u16,
+
+    /// The time (in microseconds) to wait for a response.
+    pub response_timeout: u32,
+}
+
+impl CommandWaits {
+    pub const DEFAULT: CommandWaits = CommandWaits {
+        ready_timeout: WAIT_FOR_CARD_TIMEOUT,
+        ready_delay: WAIT_FOR_CARD_DELAY,
+        response_timeout: RESPONSE_TIMEOUT,
+    };
+}
+
+/// The configuration for [`initilization_flow`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InitConfig {
+    /// The waits for each command, both during the initilization and after
+    /// it.
+    pub waits: CommandWaits,
+
+    /// The number of times to send SendIfCond while the check pattern in the
+    /// response does not match.
+    pub if_cond_count: u32,
+
+    /// The time (in microseconds) to keep sending SdSendOpCond (or
+    /// SendOpCond) while the card is idle.
+    pub op_cond_timeout: u32,
+
+    /// The delay (in microseconds) between each SdSendOpCond (or SendOpCond).
+    pub op_cond_delay: u16,
+
+    /// Whether to turn on CRC checking.
+    pub crc: CrcOption,
+
+    /// The card capacities that the host supports.
+    pub host_capacity_support: HostCapacitySupport,
+
+    /// The supply voltage (in mV) that the host provides to the card.
+    pub host_voltage: u16,
+}
+
+impl Default for InitConfig {
+    fn default() -> Self {
+        InitConfig {
+            waits: CommandWaits::DEFAULT,
+            if_cond_count: MAX_IF_COND_COUNT,
+            op_cond_timeout: OP_COND_TIMEOUT,
+            op_cond_delay: OP_COND_DELAY,
+            crc: CrcOption::On,
+            host_capacity_support: HostCapacitySupport::HcOrXcSupported,
+            host_voltage: DEFAULT_HOST_VOLTAGE,
+        }
+    }
+}
 
 #[derive(Debug, PartialEq, Snafu)]
 pub enum Error {
@@ -44,17 +147,149 @@ pub enum Error {
     #[snafu(display("Unable to transfer to and from SPI."))]
     SpiTransfer,
 
-    #[snafu(display("Timeout waiting for the card to be ready."))]
-    WaitForCardTimeout,
+    #[snafu(display(
+        "Timeout after {} microseconds waiting for the card to be ready.",
+        elapsed_us
+    ))]
+    WaitForCardTimeout { elapsed_us: u32 },
 
-    #[snafu(display("Timeout waiting for the card to respond to a command."))]
-    WaitForResponseTimeout,
+    #[snafu(display(
+        "Timeout after {} microseconds waiting for the card to respond to a command.",
+        elapsed_us
+    ))]
+    WaitForResponseTimeout { elapsed_us: u32 },
 
     #[snafu(display("The response to a command indicated an error."))]
     CommandResponse { source: ResponseError },
 
     #[snafu(display("The SD card cannot be initilizationed and is unusable."))]
     UnusableCard,
+
+    #[snafu(display(
+        "Timeout after {} microseconds waiting for the card to finish its initilization.",
+        elapsed_us
+    ))]
+    OpCondTimeout { elapsed_us: u32 },
+
+    #[snafu(display(
+        "Timeout after {} microseconds waiting for the card to send a data block.",
+        elapsed_us
+    ))]
+    WaitForDataTimeout { elapsed_us: u32 },
+
+    #[snafu(display("The card sent a data error token instead of a data block."))]
+    DataToken { source: TokenError },
+
+    #[snafu(display("The CRC for a data block from the card did not match its data."))]
+    DataCrcMismatch,
+
+    #[snafu(display("The card did not accept a data block."))]
+    DataResponse { source: TokenError },
+
+    #[snafu(display(
+        "Timeout after {} microseconds waiting for the card to stop being busy.",
+        elapsed_us
+    ))]
+    BusyTimeout { elapsed_us: u32 },
+
+    #[snafu(display(
+        "The card did not accept a data block after {} blocks were written.",
+        blocks_written
+    ))]
+    PartialWrite {
+        blocks_written: u32,
+        source: TokenError,
+    },
+
+    #[snafu(display("The status of the card indicated an error."))]
+    CardStatus { source: CardStatusError },
+
+    #[snafu(display("The last block to erase comes before the first block."))]
+    InvalidEraseRange,
+
+    #[snafu(display("Block {} is past the end of the card.", block))]
+    BlockOutOfRange { block: u32 },
+
+    #[snafu(display("The card does not support the {:?} erase.", kind))]
+    UnsupportedErase { kind: EraseKind },
+
+    #[snafu(display("The card only erases whole erase groups of {} blocks.", group_size))]
+    MisalignedErase { group_size: u32 },
+
+    #[snafu(display("The card does not support write protect groups."))]
+    UnsupportedWriteProtect,
+
+    #[snafu(display("The card is write protected."))]
+    WriteProtected,
+
+    #[snafu(display("A card password is longer than 16 bytes."))]
+    InvalidPassword,
+
+    #[snafu(display("The card does not support a supply voltage of {} mV.", host_voltage))]
+    UnsupportedVoltage {
+        host_voltage: u16,
+        window: VoltageWindow,
+    },
+
+    #[snafu(display("The card is an unsupported {:?} card.", card_type))]
+    UnsupportedCard { card_type: UnsupportedCardType },
+}
+
+/// The properties of a card that are found by [`initilization_flow`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CardProperties {
+    /// Whether the card is an SD card or an MMC card.
+    pub card_type: CardType,
+
+    /// Whether the card uses byte or block addressing.
+    pub capacity: CardCapacity,
+
+    /// The number of I/O functions of a combo card (0 for a memory card).
+    pub io_functions: u8,
+
+    /// The physical layer version found with the SendIfCond command.
+    pub version: Version,
+
+    /// The voltage window from the OCR register of the card.
+    pub voltage_window: VoltageWindow,
+
+    /// Whether CRC checking is turned on for the card.
+    pub crc_enabled: bool,
+}
+
+impl Error {
+    /// Whether the error is from a CRC mismatch on the data or on a command.
+    ///
+    /// These errors are usually caused by noise on the SPI bus, so they are
+    /// less likely at a lower clock rate.
+    pub fn is_crc_error(&self) -> bool {
+        matches!(
+            self,
+            Error::CommandResponse {
+                source: ResponseError::ComCrcError
+            } | Error::DataCrcMismatch
+                | Error::DataResponse {
+                    source: TokenError::DataCrcError
+                }
+                | Error::PartialWrite {
+                    source: TokenError::DataCrcError,
+                    ..
+                }
+        )
+    }
+
+    /// The time (in microseconds) that was spent waiting for the card if
+    /// the error is from a timeout that is measured in time.
+    pub fn elapsed_us(&self) -> Option<u32> {
+        match *self {
+            Error::WaitForCardTimeout { elapsed_us }
+            | Error::WaitForResponseTimeout { elapsed_us }
+            | Error::OpCondTimeout { elapsed_us }
+            | Error::WaitForDataTimeout { elapsed_us }
+            | Error::BusyTimeout { elapsed_us } => Some(elapsed_us),
+            _ => None,
+        }
+    }
 }
 
 /// Power up sequence from section 6.4.1 of the Simplified Specification.
@@ -75,35 +310,57 @@ pub fn power_up_card(
     Ok(())
 }
 
+/// Initilization flow from Figure 7-2 of the Simplified Specification.
+///
+/// The retry counts, delays and the options that are sent to the card are
+/// taken from `config`. An MMC card, which rejects the SdSendOpCond app command, is
+/// initialized with the SendOpCond command instead. Only the memory portion
+/// of a combo card is initialized and an SDIO card without a memory portion
+/// is refused.
 pub fn initilization_flow<SPI, DELAY>(
     spi: &mut SPI,
     delay: &mut DELAY,
-) -> Result<CardCapacity, Error>
+    config: &InitConfig,
+) -> Result<CardProperties, Error>
 where
     SPI: Write<u8> + Transfer<u8>,
-    DELAY: DelayUs<u16>,
+    DELAY: DelayTimer,
 {
     let mut command = [0; 6];
 
     // 2. GoIdleState
     cmds::go_idle_state(&mut command);
-    execute_command(spi, delay, &command)?;
+    R1Response::execute_command(spi, delay, &command, &config.waits)?;
 
     // 3. SendIfCond and check for illegal command (v1 card)
-    let version = send_if_cond(spi, delay)?;
+    let version = send_if_cond(spi, delay, config)?;
 
-    // 4. CrcOnOff to turn crc checking on
-    cmds::crc_on_off(cmds::CrcOption::On, &mut command);
-    execute_command(spi, delay, &command)?;
+    // IoSendOpCond to detect an SDIO or combo card (see section 3.1.2 of the
+    // SDIO Simplified Specification)
+    let io_functions = probe_io(spi, delay, config)?;
 
-    // 5. ReadOcr and check for compatible voltage (or assume it is in range)
-    // For now assume that the voltage is 3.3 V which is always supported.
+    // 4. CrcOnOff to turn crc checking on (or off)
+    cmds::crc_on_off(config.crc, &mut command);
+    R1Response::execute_command(spi, delay, &command, &config.waits)?;
 
-    // 6. SendOpCond (with HCR if not v1 card) repeatedly until not idle
-    send_op_cond(spi, version, delay)?;
+    // 5. ReadOcr and check for compatible voltage
+    let voltage_window = check_voltage(spi, delay, config)?;
 
-    // 7. If not v1 card then ReadOcr and check card capacity
-    check_card_capacity(spi, delay, version)
+    // 6. SendOpCond (with HCR if not v1 card) repeatedly until not idle
+    let card_type = send_op_cond(spi, version, delay, config)?;
+
+    // 7. If not v1 card (or if an MMC card) then ReadOcr and check card
+    // capacity
+    let capacity = check_card_capacity(spi, delay, version, card_type, config)?;
+
+    Ok(CardProperties {
+        card_type,
+        capacity,
+        io_functions,
+        version,
+        voltage_window,
+        crc_enabled: config.crc == CrcOption::On,
+    })
 }
 
 pub fn with_cs_low<CS, SPI, DELAY, F, O>(
@@ -116,635 +373,3362 @@ where
     CS: OutputPin,
     SPI: Write<u8>,
     DELAY: DelayUs<u16>,
-    F: Fn(&mut SPI, &mut DELAY) -> Result<O, Error>,
+    F: FnOnce(&mut SPI, &mut DELAY) -> Result<O, Error>,
 {
-    cs.set_low()
+    let result = cs
+        .set_low()
         .map_err(|_| ChipSelectSnafu {}.build())
-        .and_then(|_| f(spi, delay))
-        .and_then(|o| {
-            cs.set_high()
-                .map(|_| o)
-                .map_err(|_| ChipSelectSnafu {}.build())
-        })
-        .map_err(|e| {
+        .and_then(|_| f(spi, delay));
+
+    match result {
+        Ok(o) => cs
+            .set_high()
+            .map(|_| o)
+            .map_err(|_| ChipSelectSnafu {}.build()),
+        Err(e) => {
             // ignore the error to give priority to the error from f(spi)
             let _ = cs.set_high();
-            e
-        })
+            Err(e)
+        }
+    }
 }
 
-fn send_if_cond<SPI, DELAY>(spi: &mut SPI, delay: &mut DELAY) -> Result<Version, Error>
+/// Read the CSD register of an initialized card of type `card_type`.
+pub fn read_csd<SPI, DELAY>(
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    waits: &CommandWaits,
+    card_type: CardType,
+) -> Result<Csd, Error>
 where
     SPI: Write<u8> + Transfer<u8>,
-    DELAY: DelayUs<u16>,
+    DELAY: DelayTimer,
 {
     let mut command = [0; 6];
-    let check_pattern = common::IF_COND_CHECK_PATTERN;
-
-    for _ in 0..MAX_IF_COND_COUNT {
-        let mut retry = false;
+    let mut csd = [0; 16];
 
-        cmds::send_if_cond(check_pattern, &mut command);
-        let result = R7Response::execute_command(spi, delay, &command)
-            .map(|r7| {
-                if r7.check(check_pattern).is_err() {
-                    retry = true;
-                }
-                Version::V2
-            })
-            .or_else(|err| match err {
-                Error::CommandResponse { source } if source == ResponseError::IllegalCommand => {
-                    Ok(Version::V1)
-                }
-                _ => Err(err),
-            });
+    cmds::send_csd(&mut command);
+    read_register(spi, delay, waits, &command, &mut csd)?;
 
-        if !retry {
-            return result;
-        }
+    match card_type {
+        CardType::Sd => Csd::new(csd),
+        CardType::Mmc => Csd::new_mmc(csd),
     }
-
-    UnusableCardSnafu {}.fail()
+    .context(CommandResponseSnafu {})
 }
 
-fn send_op_cond<SPI>(
+/// Read the CID register of an initialized card.
+pub fn read_cid<SPI, DELAY>(
     spi: &mut SPI,
-    version: Version,
-    delay: &mut impl DelayUs<u16>,
-) -> Result<(), Error>
+    delay: &mut DELAY,
+    waits: &CommandWaits,
+) -> Result<Cid, Error>
 where
     SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
 {
     let mut command = [0; 6];
+    let mut cid = [0; 16];
 
-    for _ in 0..MAX_OP_COND_COUNT {
-        cmds::app_cmd(&mut command);
-        execute_command(spi, delay, &command)?;
+    cmds::send_cid(&mut command);
+    read_register(spi, delay, waits, &command, &mut cid)?;
 
-        cmds::sd_send_op_cond(version.into(), &mut command);
-        let r1 = execute_command(spi, delay, &command)?;
+    Cid::new(cid).context(CommandResponseSnafu {})
+}
 
-        if r1 & R1Response::IDLE == R1Response::NONE {
-            return Ok(());
-        }
+/// Read the SCR register of an initialized card.
+pub fn read_scr<SPI, DELAY>(
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    waits: &CommandWaits,
+) -> Result<Scr, Error>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    let mut command = [0; 6];
+    let mut scr = [0; 8];
 
-        delay.delay_us(OP_COND_DELAY)
-    }
+    cmds::app_cmd(&mut command);
+    execute_command(spi, delay, &command, waits)?;
+    cmds::send_scr(&mut command);
+    read_register(spi, delay, waits, &command, &mut scr)?;
 
-    UnusableCardSnafu {}.fail()
+    Scr::new(scr).context(CommandResponseSnafu {})
 }
 
-fn check_card_capacity<SPI, DELAY>(
+/// Read the SD Status of an initialized card.
+pub fn read_sd_status<SPI, DELAY>(
     spi: &mut SPI,
     delay: &mut DELAY,
-    version: Version,
-) -> Result<CardCapacity, Error>
+    waits: &CommandWaits,
+) -> Result<SdStatus, Error>
 where
     SPI: Write<u8> + Transfer<u8>,
-    DELAY: DelayUs<u16>,
+    DELAY: DelayTimer,
 {
-    match version {
-        Version::V1 => Ok(CardCapacity::Standard),
-        Version::V2 => {
-            let mut command = [0; 6];
-
-            cmds::read_ocr(&mut command);
-            R3Response::execute_command(spi, delay, &command).map(|r3| r3.card_capacity())
-        }
-    }
+    let mut command = [0; 6];
+    let mut status = [0; 64];
+
+    cmds::app_cmd(&mut command);
+    execute_command(spi, delay, &command, waits)?;
+    cmds::sd_status(&mut command);
+    // A locked card still sends its SD Status (see section 4.3.7.6).
+    R2Response::execute_command(spi, delay, &command, waits)?
+        .without_lock()
+        .check_error()
+        .context(CardStatusSnafu {})?;
+    read_data(spi, delay, &mut status)?;
+
+    Ok(SdStatus::new(status))
 }
 
-#[derive(Debug, Clone, Copy)]
-enum Version {
-    V1,
-    V2,
-}
+/// Erase the blocks from `start_block` to `end_block` (inclusive) using the
+/// erase function `kind`.
+///
+/// The card is allowed `busy_timeout` microseconds to finish the erase. An
+/// MMC card uses the erase group commands, so the range should cover whole
+/// erase groups.
+#[allow(clippy::too_many_arguments)]
+pub fn erase<SPI, DELAY>(
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    waits: &CommandWaits,
+    capacity: CardCapacity,
+    card_type: CardType,
+    start_block: u32,
+    end_block: u32,
+    kind: EraseKind,
+    busy_timeout: u32,
+) -> Result<(), Error>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    ensure!(start_block <= end_block, InvalidEraseRangeSnafu);
 
-impl From<Version> for HostCapacitySupport {
-    fn from(version: Version) -> Self {
-        match version {
-            Version::V1 => HostCapacitySupport::ScOnly,
-            Version::V2 => HostCapacitySupport::HcOrXcSupported,
-        }
+    let start_address = data_address(capacity, start_block)?;
+    let end_address = data_address(capacity, end_block)?;
+    let mut command = [0; 6];
+
+    // This is the erase sequence from section 4.3.5.
+    match card_type {
+        CardType::Sd => cmds::erase_wr_blk_start_addr(start_address, &mut command),
+        CardType::Mmc => cmds::erase_group_start(start_address, &mut command),
+    }
+    execute_command(spi, delay, &command, waits)?;
+    match card_type {
+        CardType::Sd => cmds::erase_wr_blk_end_addr(end_address, &mut command),
+        CardType::Mmc => cmds::erase_group_end(end_address, &mut command),
     }
+    execute_command(spi, delay, &command, waits)?;
+    cmds::erase(kind, &mut command);
+    R1bResponse::execute_command_with_busy_timeout(spi, delay, &command, busy_timeout, waits)?;
+
+    Ok(())
 }
 
-fn execute_command<SPI, DELAY>(
+/// Replace the CSD register of an initialized card with `csd`.
+///
+/// Only the programmable part of the CSD register can be changed (see
+/// section 5.3).
+pub fn program_csd<SPI, DELAY>(
     spi: &mut SPI,
     delay: &mut DELAY,
-    cmd: &[u8],
-) -> Result<R1Response, Error>
+    waits: &CommandWaits,
+    csd: &Csd,
+) -> Result<(), Error>
 where
     SPI: Write<u8> + Transfer<u8>,
-    DELAY: DelayUs<u16>,
+    DELAY: DelayTimer,
 {
-    R1Response::execute_command(spi, delay, cmd)
+    let mut command = [0; 6];
+
+    cmds::program_csd(&mut command);
+    execute_command(spi, delay, &command, waits)?;
+    write_data(
+        spi,
+        delay,
+        tokens::START_BLOCK,
+        csd.as_bytes(),
+        DEFAULT_BUSY_TIMEOUT,
+    )?;
+
+    // The card only reports a rejected CSD through the CSD_OVERWRITE bit of
+    // its status.
+    send_status(spi, delay, waits)
 }
 
-trait Execute
+/// Set the write protection of the write protect group that contains the
+/// data block at index `block`.
+pub fn set_write_prot<SPI, DELAY>(
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    waits: &CommandWaits,
+    capacity: CardCapacity,
+    block: u32,
+) -> Result<(), Error>
 where
-    Self: Sized,
+    SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
 {
-    fn execute_command<SPI, DELAY>(
-        spi: &mut SPI,
-        delay: &mut DELAY,
-        cmd: &[u8],
-    ) -> Result<Self, Error>
-    where
-        SPI: Write<u8> + Transfer<u8>,
-        DELAY: DelayUs<u16>;
-}
-
-impl<R: Response> Execute for R {
-    fn execute_command<SPI, DELAY>(
-        spi: &mut SPI,
-        delay: &mut DELAY,
-        cmd: &[u8],
-    ) -> Result<Self, Error>
-    where
-        SPI: Write<u8> + Transfer<u8>,
-        DELAY: DelayUs<u16>,
-    {
-        debug_assert_eq!(cmd.len(), 6);
+    let mut command = [0; 6];
 
-        wait_for_card(spi, delay)?;
+    cmds::set_write_prot(data_address(capacity, block)?, &mut command);
+    R1bResponse::execute_command(spi, delay, &command, waits)?;
 
-        spi.write(cmd).map_err(|_| SpiWriteSnafu {}.build())?;
+    Ok(())
+}
 
-        for _ in 0..MAX_WAIT_FOR_RESPONSE {
-            let recv = receive(spi)?;
-            if recv != 0xff {
-                let r1 = R1Response::new(recv);
-                let mut extra = R::ExtraBytes::default();
-                if !r1.response_truncated() {
-                    for e in extra.as_mut().iter_mut() {
-                        *e = receive(spi)?;
-                    }
-                }
+/// Clear the write protection of the write protect group that contains the
+/// data block at index `block`.
+pub fn clr_write_prot<SPI, DELAY>(
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    waits: &CommandWaits,
+    capacity: CardCapacity,
+    block: u32,
+) -> Result<(), Error>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    let mut command = [0; 6];
 
-                return r1
-                    .check_error()
-                    .context(CommandResponseSnafu {})
-                    .map(|r1| R::create(r1, &extra));
-            }
-        }
+    cmds::clr_write_prot(data_address(capacity, block)?, &mut command);
+    R1bResponse::execute_command(spi, delay, &command, waits)?;
 
-        WaitForResponseTimeoutSnafu {}.fail()
-    }
+    Ok(())
 }
 
-fn wait_for_card<SPI, DELAY>(spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error>
+/// Read the write protection bits of the 32 write protect groups starting
+/// with the group that contains the data block at index `block`.
+///
+/// The least significant bit is the write protection bit of the first group.
+pub fn send_write_prot<SPI, DELAY>(
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    waits: &CommandWaits,
+    capacity: CardCapacity,
+    block: u32,
+) -> Result<u32, Error>
 where
-    SPI: Transfer<u8>,
-    DELAY: DelayUs<u16>,
+    SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
 {
-    for _ in 0..WAIT_FOR_CARD_COUNT {
-        if receive(spi)? == 0xff {
-            return Ok(());
-        }
+    let mut command = [0; 6];
+    let mut bits = [0; 4];
 
-        delay.delay_us(WAIT_FOR_CARD_DELAY);
-    }
+    cmds::send_write_prot(data_address(capacity, block)?, &mut command);
+    read_register(spi, delay, waits, &command, &mut bits)?;
 
-    WaitForCardTimeoutSnafu {}.fail()
+    Ok(u32::from_be_bytes(bits))
 }
 
-fn receive<SPI: Transfer<u8>>(spi: &mut SPI) -> Result<u8, Error> {
-    let mut buffer = [0xff];
-    let response = spi
-        .transfer(&mut buffer)
-        .map_err(|_| SpiTransferSnafu {}.build())?;
+/// Read the card status of an initialized card and check it for errors.
+pub fn send_status<SPI, DELAY>(
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    waits: &CommandWaits,
+) -> Result<(), Error>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    read_status(spi, delay, waits)?
+        .check_error()
+        .context(CardStatusSnafu {})
+}
 
-    Ok(response[0])
+/// Check whether an initialized card is locked by a password.
+pub fn is_locked<SPI, DELAY>(
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    waits: &CommandWaits,
+) -> Result<bool, Error>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    Ok(read_status(spi, delay, waits)?.is_locked())
 }
 
-#[cfg(test)]
-mod test {
-    use std::{io::ErrorKind, iter};
+/// Execute the password operation `op` and return whether the card is locked
+/// afterwards.
+///
+/// This follows section 4.3.7 of the Simplified Specification. The block
+/// length is set to the length of the LockUnlock data block for the command
+/// and is then set back to `BLOCK_SIZE`.
+pub fn lock_unlock<SPI, DELAY>(
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    waits: &CommandWaits,
+    op: LockUnlockOp<'_>,
+) -> Result<bool, Error>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    let (first, second) = op.passwords();
+    ensure!(
+        first.len() <= cmds::MAX_PASSWORD_LEN && second.len() <= cmds::MAX_PASSWORD_LEN,
+        InvalidPasswordSnafu
+    );
+
+    let mut data = [0; cmds::MAX_LOCK_UNLOCK_DATA_LEN];
+    let len = op.encode_data(&mut data);
+    let busy_timeout = match op {
+        LockUnlockOp::ForceErase => FORCE_ERASE_TIMEOUT,
+        _ => DEFAULT_BUSY_TIMEOUT,
+    };
+    let mut command = [0; 6];
 
-    use crate::{common, testutils::StubSpi};
+    cmds::set_block_len(len as u32, &mut command);
+    execute_command(spi, delay, &command, waits)?;
+    cmds::lock_unlock(&mut command);
+    let result = execute_command(spi, delay, &command, waits)
+        .and_then(|_| write_data(spi, delay, tokens::START_BLOCK, &data[..len], busy_timeout));
+
+    cmds::set_block_len(BLOCK_SIZE as u32, &mut command);
+    let restore = execute_command(spi, delay, &command, waits);
+    result?;
+    restore?;
+
+    // The card only reports a failed LockUnlock command through its status.
+    let status = read_status(spi, delay, waits)?;
+    status
+        .without_lock()
+        .check_error()
+        .context(CardStatusSnafu {})?;
+
+    Ok(status.is_locked())
+}
 
-    use embedded_hal_mock::{delay, pin, spi, MockError};
+fn read_status<SPI, DELAY>(
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    waits: &CommandWaits,
+) -> Result<R2Response, Error>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    let mut command = [0; 6];
 
-    use super::*;
+    cmds::send_status(&mut command);
+    R2Response::execute_command(spi, delay, &command, waits)
+}
 
-    #[test]
-    fn power_up_card_has_74_clocks_with_cs_high() {
-        let mut spi = spi::Mock::new(&[spi::Transaction::write([0xff; 10].to_vec())]);
-        let mut cs = pin::Mock::new(&[pin::Transaction::set(pin::State::High)]);
-        let mut delay = delay::MockNoop::new();
+/// Switch an initialized card to High Speed mode if the card supports it.
+///
+/// This returns whether the card is now in High Speed mode. It follows the
+/// sequence in section 4.3.10 of the Simplified Specification: the card is
+/// first asked whether it can switch function group 1 (the access mode) to
+/// High Speed and it is only switched if it can.
+pub fn switch_to_high_speed<SPI, DELAY>(
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    waits: &CommandWaits,
+) -> Result<bool, Error>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    const ACCESS_MODE: usize = 1;
+    const HIGH_SPEED: u8 = 1;
 
-        power_up_card(&mut spi, &mut cs, &mut delay).expect("Unable to power up");
+    let mut command = [0; 6];
+    let mut status = [0; 64];
 
-        spi.done();
-        cs.done();
+    cmds::switch_func(SwitchFuncMode::Check, HIGH_SPEED, &mut command);
+    read_register(spi, delay, waits, &command, &mut status)?;
+    let check = SwitchStatus::new(status);
+    if !check.supports_function(ACCESS_MODE, HIGH_SPEED.into())
+        || check.selected_function(ACCESS_MODE) != HIGH_SPEED
+    {
+        return Ok(false);
     }
 
-    #[test]
-    fn power_up_card_handles_cs_high_error() {
-        let go_high = pin::Transaction::set(pin::State::High)
-            .with_error(MockError::Io(ErrorKind::Unsupported));
-        let mut spi = spi::Mock::new(&[spi::Transaction::write([0xff; 10].to_vec())]);
-        let mut cs = pin::Mock::new(&[go_high]);
-        let mut delay = delay::MockNoop::new();
-
-        let result = power_up_card(&mut spi, &mut cs, &mut delay);
+    cmds::switch_func(SwitchFuncMode::Switch, HIGH_SPEED, &mut command);
+    read_register(spi, delay, waits, &command, &mut status)?;
 
-        assert_eq!(result, Err(Error::ChipSelect));
-    }
+    Ok(SwitchStatus::new(status).selected_function(ACCESS_MODE) == HIGH_SPEED)
+}
 
-    #[test]
-    fn with_cs_low_toggles_cs() {
-        let set_low = pin::Transaction::set(pin::State::Low);
-        let set_high = pin::Transaction::set(pin::State::High);
-        let mut cs = pin::Mock::new(&[set_low, set_high]);
+/// Execute a command that is answered with the contents of a register in a
+/// data block.
+fn read_register<SPI, DELAY>(
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    waits: &CommandWaits,
+    command: &[u8],
+    buffer: &mut [u8],
+) -> Result<(), Error>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    execute_command(spi, delay, command, waits)?;
+    read_data(spi, delay, buffer)
+}
+
+/// Read `bytes.len()` bytes starting at the byte offset `offset`.
+///
+/// This reads all of the data blocks that overlap the requested range (see
+/// [`read_blocks`]). Only the requested bytes of a partially covered data
+/// block are copied to `bytes`.
+pub fn read<SPI, DELAY>(
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    waits: &CommandWaits,
+    capacity: CardCapacity,
+    offset: u32,
+    bytes: &mut [u8],
+) -> Result<(), Error>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    let layout = BlockLayout::new(offset, bytes.len());
+    let (head, rest) = bytes.split_at_mut(layout.head_len);
+    let (middle, tail) = rest.split_at_mut(layout.middle_len);
+    let mut head_buffer = [0; BLOCK_SIZE];
+    let mut tail_buffer = [0; BLOCK_SIZE];
+
+    let head_block = if head.is_empty() {
+        None
+    } else {
+        Some(&mut head_buffer[..])
+    };
+    let tail_block = if tail.is_empty() {
+        None
+    } else {
+        Some(&mut tail_buffer[..])
+    };
+    let blocks = head_block
+        .into_iter()
+        .chain(middle.chunks_mut(BLOCK_SIZE))
+        .chain(tail_block);
+
+    read_blocks(
+        spi,
+        delay,
+        waits,
+        capacity,
+        layout.block,
+        layout.count,
+        blocks,
+    )?;
+
+    head.copy_from_slice(&head_buffer[layout.start..layout.start + head.len()]);
+    tail.copy_from_slice(&tail_buffer[..tail.len()]);
+
+    Ok(())
+}
+
+/// Read `count` consecutive data blocks, starting with the data block at
+/// index `block`, into the buffers from `blocks`.
+///
+/// This uses a ReadSingleBlock command for a single data block or a
+/// ReadMultipleBlock command followed by a StopTransmission command for more
+/// than one data block (see section 7.2.3). Each of the buffers from `blocks`
+/// must be `BLOCK_SIZE` bytes long.
+pub fn read_blocks<'a, SPI, DELAY>(
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    waits: &CommandWaits,
+    capacity: CardCapacity,
+    block: u32,
+    count: u32,
+    mut blocks: impl Iterator<Item = &'a mut [u8]>,
+) -> Result<(), Error>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    if count == 0 {
+        return Ok(());
+    }
+
+    let mut command = [0; 6];
+    let address = data_address(capacity, block)?;
+    let multiple = count > 1;
+
+    if multiple {
+        cmds::read_multiple_block(address, &mut command);
+    } else {
+        cmds::read_single_block(address, &mut command);
+    }
+    execute_command(spi, delay, &command, waits)?;
+
+    let result = blocks.try_for_each(|buffer| {
+        debug_assert_eq!(buffer.len(), BLOCK_SIZE);
+        read_data(spi, delay, buffer)
+    });
+
+    if multiple {
+        // stop the transmission even after an error so that the card is
+        // ready for the next command
+        let stopped = stop_transmission(spi, delay, waits);
+        result.and(stopped)
+    } else {
+        result
+    }
+}
+
+/// Write `bytes` starting at the byte offset `offset`.
+///
+/// This writes all of the data blocks that overlap the requested range (see
+/// [`write_blocks`]). A partially covered data block is first read from the
+/// card so that the bytes outside of the requested range are written back
+/// unchanged.
+pub fn write<SPI, DELAY>(
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    waits: &CommandWaits,
+    capacity: CardCapacity,
+    card_type: CardType,
+    offset: u32,
+    bytes: &[u8],
+) -> Result<(), Error>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    let layout = BlockLayout::new(offset, bytes.len());
+    let (head, rest) = bytes.split_at(layout.head_len);
+    let (middle, tail) = rest.split_at(layout.middle_len);
+    let mut head_buffer = [0; BLOCK_SIZE];
+    let mut tail_buffer = [0; BLOCK_SIZE];
+
+    // The partially covered data blocks are read before anything is written
+    // since the card can't be read from in the middle of a multiple block
+    // write.
+    let head_block = if head.is_empty() {
+        None
+    } else {
+        read_block(spi, delay, waits, capacity, layout.block, &mut head_buffer)?;
+        head_buffer[layout.start..layout.start + head.len()].copy_from_slice(head);
+        Some(&head_buffer[..])
+    };
+    let tail_block = if tail.is_empty() {
+        None
+    } else {
+        let block = layout.block + layout.count - 1;
+        read_block(spi, delay, waits, capacity, block, &mut tail_buffer)?;
+        tail_buffer[..tail.len()].copy_from_slice(tail);
+        Some(&tail_buffer[..])
+    };
+    let blocks = head_block
+        .into_iter()
+        .chain(middle.chunks(BLOCK_SIZE))
+        .chain(tail_block);
+
+    write_blocks(
+        spi,
+        delay,
+        waits,
+        capacity,
+        card_type,
+        layout.block,
+        layout.count,
+        blocks,
+    )
+}
+
+/// Write `count` consecutive data blocks, starting with the data block at
+/// index `block`, from the buffers from `blocks`.
+///
+/// This uses a WriteBlock command for a single data block or a
+/// WriteMultipleBlock command for more than one data block (see section
+/// 7.2.4). Each of the buffers from `blocks` must be `BLOCK_SIZE` bytes long.
+/// An MMC card does not have the app commands to pre-erase the blocks or to
+/// report how many blocks were written, so those steps are skipped for
+/// `CardType::Mmc`.
+#[allow(clippy::too_many_arguments)]
+pub fn write_blocks<'a, SPI, DELAY>(
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    waits: &CommandWaits,
+    capacity: CardCapacity,
+    card_type: CardType,
+    block: u32,
+    count: u32,
+    mut blocks: impl Iterator<Item = &'a [u8]>,
+) -> Result<(), Error>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    let result = match count {
+        0 => Ok(()),
+        1 => blocks.try_for_each(|data| write_block(spi, delay, waits, capacity, block, data)),
+        _ => write_multiple_block(spi, delay, waits, capacity, card_type, block, count, blocks),
+    };
+
+    // A write error may be caused by writing to a write protected block,
+    // which the card only reports through its status (see section 4.10.1).
+    match result {
+        Err(Error::DataResponse {
+            source: TokenError::WriteError,
+        })
+        | Err(Error::PartialWrite {
+            source: TokenError::WriteError,
+            ..
+        }) => match send_status(spi, delay, waits) {
+            Err(
+                e @ Error::CardStatus {
+                    source: CardStatusError::WpViolation,
+                },
+            ) => Err(e),
+            _ => result,
+        },
+        _ => result,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn write_multiple_block<'a, SPI, DELAY>(
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    waits: &CommandWaits,
+    capacity: CardCapacity,
+    card_type: CardType,
+    block: u32,
+    count: u32,
+    mut blocks: impl Iterator<Item = &'a [u8]>,
+) -> Result<(), Error>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    let address = data_address(capacity, block)?;
+    let mut command = [0; 6];
+
+    // Let the card pre-erase the blocks we are about to write.
+    if card_type == CardType::Sd {
+        cmds::app_cmd(&mut command);
+        execute_command(spi, delay, &command, waits)?;
+        cmds::set_wr_blk_erase_count(count, &mut command);
+        execute_command(spi, delay, &command, waits)?;
+    }
+
+    cmds::write_multiple_block(address, &mut command);
+    execute_command(spi, delay, &command, waits)?;
+
+    let busy_timeout = capacity.write_timeout();
+    let result = blocks.try_for_each(|data| {
+        write_data(
+            spi,
+            delay,
+            tokens::START_MULTIPLE_BLOCK_WRITE,
+            data,
+            busy_timeout,
+        )
+    });
+
+    match result {
+        Ok(()) => stop_tran(spi, delay, busy_timeout),
+        Err(Error::DataResponse { source }) if card_type == CardType::Sd => {
+            // After a rejected data block the card can tell us how many of
+            // the blocks were actually written (see section 7.3.3.1).
+            stop_tran(spi, delay, busy_timeout)?;
+            let blocks_written = send_num_wr_blocks(spi, delay, waits)?;
+            Err(source).context(PartialWriteSnafu { blocks_written })
+        }
+        Err(e) => {
+            // ignore the error to give priority to the original error
+            let _ = stop_tran(spi, delay, busy_timeout);
+            Err(e)
+        }
+    }
+}
+
+fn stop_tran<SPI, DELAY>(spi: &mut SPI, delay: &mut DELAY, busy_timeout: u32) -> Result<(), Error>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    // The card starts signalling busy one byte after the stop tran token
+    // (see Figure 7-7).
+    spi.write(&[tokens::STOP_TRAN])
+        .map_err(|_| SpiWriteSnafu {}.build())?;
+    receive(spi)?;
+
+    wait_while_busy(spi, delay, busy_timeout)
+}
+
+fn send_num_wr_blocks<SPI, DELAY>(
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    waits: &CommandWaits,
+) -> Result<u32, Error>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    let mut command = [0; 6];
+    let mut blocks = [0; 4];
+
+    cmds::app_cmd(&mut command);
+    execute_command(spi, delay, &command, waits)?;
+    cmds::send_num_wr_blocks(&mut command);
+    execute_command(spi, delay, &command, waits)?;
+    read_data(spi, delay, &mut blocks)?;
+
+    Ok(u32::from_be_bytes(blocks))
+}
+
+fn read_block<SPI, DELAY>(
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    waits: &CommandWaits,
+    capacity: CardCapacity,
+    block: u32,
+    buffer: &mut [u8],
+) -> Result<(), Error>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    read_blocks(spi, delay, waits, capacity, block, 1, iter::once(buffer))
+}
+
+fn read_data<SPI, DELAY>(spi: &mut SPI, delay: &mut DELAY, buffer: &mut [u8]) -> Result<(), Error>
+where
+    SPI: Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    wait_for_start_block(spi, delay)?;
+
+    receive_into(spi, buffer)?;
+    let mut crc = [0; 2];
+    receive_into(spi, &mut crc)?;
+
+    ensure!(
+        u16::from_be_bytes(crc) == tokens::crc16(buffer),
+        DataCrcMismatchSnafu
+    );
+
+    Ok(())
+}
+
+fn stop_transmission<SPI, DELAY>(
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    waits: &CommandWaits,
+) -> Result<(), Error>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    let mut command = [0; 6];
+
+    // The card is still sending data so we can't wait for it to be ready
+    // before sending the command. The byte after the command is a stuff byte
+    // which is followed by the R1b response (see Figure 7-5).
+    cmds::stop_transmission(&mut command);
+    spi.write(&command).map_err(|_| SpiWriteSnafu {}.build())?;
+    receive(spi)?;
+
+    R1bResponse::receive_response(spi, delay, DEFAULT_BUSY_TIMEOUT, waits)?;
+    Ok(())
+}
+
+fn write_block<SPI, DELAY>(
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    waits: &CommandWaits,
+    capacity: CardCapacity,
+    block: u32,
+    buffer: &[u8],
+) -> Result<(), Error>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    debug_assert_eq!(buffer.len(), BLOCK_SIZE);
+
+    let mut command = [0; 6];
+
+    cmds::write_block(data_address(capacity, block)?, &mut command);
+    execute_command(spi, delay, &command, waits)?;
+    write_data(
+        spi,
+        delay,
+        tokens::START_BLOCK,
+        buffer,
+        capacity.write_timeout(),
+    )
+}
+
+fn write_data<SPI, DELAY>(
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    token: u8,
+    buffer: &[u8],
+    busy_timeout: u32,
+) -> Result<(), Error>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    let crc = tokens::crc16(buffer).to_be_bytes();
+
+    // The card needs at least one byte between the command response and the
+    // start block token (see section 7.5.2.2).
+    spi.write(&[0xff, token])
+        .map_err(|_| SpiWriteSnafu {}.build())?;
+    spi.write(buffer).map_err(|_| SpiWriteSnafu {}.build())?;
+    spi.write(&crc).map_err(|_| SpiWriteSnafu {}.build())?;
+
+    DataResponseToken::new(receive(spi)?)
+        .check_error()
+        .context(DataResponseSnafu {})?;
+
+    wait_while_busy(spi, delay, busy_timeout)
+}
+
+fn wait_while_busy<SPI, DELAY>(spi: &mut SPI, delay: &mut DELAY, timeout: u32) -> Result<(), Error>
+where
+    SPI: Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    let mut deadline = Deadline::new(delay, timeout);
+
+    loop {
+        if receive(spi)? != 0x00 {
+            return Ok(());
+        }
+
+        deadline
+            .delay(delay, WAIT_WHILE_BUSY_DELAY)
+            .map_err(|elapsed_us| BusyTimeoutSnafu { elapsed_us }.build())?;
+    }
+}
+
+fn wait_for_start_block<SPI, DELAY>(spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error>
+where
+    SPI: Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    let mut deadline = Deadline::new(delay, READ_TIMEOUT);
+
+    loop {
+        let recv = receive(spi)?;
+        if recv == tokens::START_BLOCK {
+            return Ok(());
+        }
+        if let Some(token) = DataErrorToken::from_byte(recv) {
+            return token.check_error().context(DataTokenSnafu {});
+        }
+
+        deadline
+            .delay(delay, WAIT_FOR_DATA_DELAY)
+            .map_err(|elapsed_us| WaitForDataTimeoutSnafu { elapsed_us }.build())?;
+    }
+}
+
+fn send_if_cond<SPI, DELAY>(
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    config: &InitConfig,
+) -> Result<Version, Error>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    let mut command = [0; 6];
+    let check_pattern = common::IF_COND_CHECK_PATTERN;
+
+    for _ in 0..config.if_cond_count {
+        let mut retry = false;
+
+        cmds::send_if_cond(check_pattern, &mut command);
+        let result = R7Response::execute_command(spi, delay, &command, &config.waits)
+            .map(|r7| {
+                if r7.check(check_pattern).is_err() {
+                    retry = true;
+                }
+                Version::V2
+            })
+            .or_else(|err| match err {
+                Error::CommandResponse {
+                    source: ResponseError::IllegalCommand,
+                } => Ok(Version::V1),
+                _ => Err(err),
+            });
+
+        if !retry {
+            return result;
+        }
+    }
+
+    UnusableCardSnafu {}.fail()
+}
+
+fn probe_io<SPI, DELAY>(spi: &mut SPI, delay: &mut DELAY, config: &InitConfig) -> Result<u8, Error>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    let mut command = [0; 6];
+
+    cmds::io_send_op_cond(&mut command);
+    match R4Response::execute_command(spi, delay, &command, &config.waits) {
+        // A memory card does not have an I/O portion.
+        Err(Error::CommandResponse {
+            source: ResponseError::IllegalCommand,
+        }) => Ok(0),
+        Err(e) => Err(e),
+        Ok(r4) => {
+            let io_functions = r4.io_functions();
+            ensure!(
+                io_functions == 0 || r4.memory_present(),
+                UnsupportedCardSnafu {
+                    card_type: UnsupportedCardType::Sdio
+                }
+            );
+
+            Ok(io_functions)
+        }
+    }
+}
+
+fn check_voltage<SPI, DELAY>(
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    config: &InitConfig,
+) -> Result<VoltageWindow, Error>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    let mut command = [0; 6];
+    let host_voltage = config.host_voltage;
+
+    cmds::read_ocr(&mut command);
+    let window = R3Response::execute_command(spi, delay, &command, &config.waits)?.voltage_window();
+    ensure!(
+        window.contains(host_voltage),
+        UnsupportedVoltageSnafu {
+            host_voltage,
+            window
+        }
+    );
+
+    Ok(window)
+}
+
+fn send_op_cond<SPI>(
+    spi: &mut SPI,
+    version: Version,
+    delay: &mut impl DelayTimer,
+    config: &InitConfig,
+) -> Result<CardType, Error>
+where
+    SPI: Write<u8> + Transfer<u8>,
+{
+    let mut command = [0; 6];
+    let waits = &config.waits;
+    let hcs = match config.host_capacity_support {
+        HostCapacitySupport::ScOnly => HostCapacitySupport::ScOnly,
+        HostCapacitySupport::HcOrXcSupported => version.into(),
+    };
+    let mut deadline = Deadline::new(delay, config.op_cond_timeout);
+
+    loop {
+        cmds::app_cmd(&mut command);
+        let result = R1Response::execute_command(spi, delay, &command, waits).and_then(|_| {
+            cmds::sd_send_op_cond(hcs, &mut command);
+            R1Response::execute_command(spi, delay, &command, waits)
+        });
+
+        let r1 = match result {
+            Err(Error::CommandResponse {
+                source: ResponseError::IllegalCommand,
+            }) => return mmc_send_op_cond(spi, delay, config, deadline),
+            r1 => r1?,
+        };
+
+        if r1 & R1Response::IDLE == R1Response::NONE {
+            return Ok(CardType::Sd);
+        }
+
+        deadline
+            .delay(delay, config.op_cond_delay)
+            .map_err(|elapsed_us| OpCondTimeoutSnafu { elapsed_us }.build())?;
+    }
+}
+
+fn mmc_send_op_cond<SPI>(
+    spi: &mut SPI,
+    delay: &mut impl DelayTimer,
+    config: &InitConfig,
+    mut deadline: Deadline,
+) -> Result<CardType, Error>
+where
+    SPI: Write<u8> + Transfer<u8>,
+{
+    let mut command = [0; 6];
+
+    loop {
+        cmds::send_op_cond(&mut command);
+        let r1 = R1Response::execute_command(spi, delay, &command, &config.waits)?;
+
+        if r1 & R1Response::IDLE == R1Response::NONE {
+            return Ok(CardType::Mmc);
+        }
+
+        deadline
+            .delay(delay, config.op_cond_delay)
+            .map_err(|elapsed_us| OpCondTimeoutSnafu { elapsed_us }.build())?;
+    }
+}
+
+fn check_card_capacity<SPI, DELAY>(
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    version: Version,
+    card_type: CardType,
+    config: &InitConfig,
+) -> Result<CardCapacity, Error>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    let mut command = [0; 6];
+
+    match (card_type, version) {
+        (CardType::Sd, Version::V1) => Ok(CardCapacity::Standard),
+        (CardType::Sd, Version::V2) => {
+            cmds::read_ocr(&mut command);
+            R3Response::execute_command(spi, delay, &command, &config.waits)
+                .map(|r3| r3.card_capacity())
+        }
+        (CardType::Mmc, _) => {
+            // The R1 response to SendOpCond in SPI mode doesn't include the
+            // OCR, so its access mode bits are read with ReadOcr. A high
+            // capacity MMC card uses sector addressing and only reports its
+            // size in the EXT_CSD register, which isn't supported.
+            cmds::read_ocr(&mut command);
+            let r3 = R3Response::execute_command(spi, delay, &command, &config.waits)?;
+            ensure!(
+                r3.card_capacity() == CardCapacity::Standard,
+                UnsupportedCardSnafu {
+                    card_type: UnsupportedCardType::SectorModeMmc
+                }
+            );
+
+            Ok(CardCapacity::Standard)
+        }
+    }
+}
+
+impl From<Version> for HostCapacitySupport {
+    fn from(version: Version) -> Self {
+        match version {
+            Version::V1 => HostCapacitySupport::ScOnly,
+            Version::V2 => HostCapacitySupport::HcOrXcSupported,
+        }
+    }
+}
+
+/// The data address argument for the data block with index `block`.
+fn data_address(capacity: CardCapacity, block: u32) -> Result<u32, Error> {
+    capacity
+        .data_address(block)
+        .context(BlockOutOfRangeSnafu { block })
+}
+
+fn execute_command<SPI, DELAY>(
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    cmd: &[u8],
+    waits: &CommandWaits,
+) -> Result<R1Response, Error>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    R1Response::execute_command(spi, delay, cmd, waits)
+}
+
+trait Execute
+where
+    Self: Sized,
+{
+    /// Execute `cmd` using `waits` to wait for the card to be ready and then
+    /// for its response.
+    fn execute_command<SPI, DELAY>(
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        cmd: &[u8],
+        waits: &CommandWaits,
+    ) -> Result<Self, Error>
+    where
+        SPI: Write<u8> + Transfer<u8>,
+        DELAY: DelayTimer,
+    {
+        Self::execute_command_with_busy_timeout(spi, delay, cmd, DEFAULT_BUSY_TIMEOUT, waits)
+    }
+
+    /// Execute `cmd` and wait at most `busy_timeout` microseconds for the
+    /// card to stop signaling busy after the response (for responses that
+    /// have a busy signal).
+    fn execute_command_with_busy_timeout<SPI, DELAY>(
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        cmd: &[u8],
+        busy_timeout: u32,
+        waits: &CommandWaits,
+    ) -> Result<Self, Error>
+    where
+        SPI: Write<u8> + Transfer<u8>,
+        DELAY: DelayTimer,
+    {
+        Self::execute(spi, delay, cmd, busy_timeout, waits)
+    }
+
+    fn execute<SPI, DELAY>(
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        cmd: &[u8],
+        busy_timeout: u32,
+        waits: &CommandWaits,
+    ) -> Result<Self, Error>
+    where
+        SPI: Write<u8> + Transfer<u8>,
+        DELAY: DelayTimer;
+
+    fn receive_response<SPI, DELAY>(
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        busy_timeout: u32,
+        waits: &CommandWaits,
+    ) -> Result<Self, Error>
+    where
+        SPI: Transfer<u8>,
+        DELAY: DelayTimer;
+}
+
+impl<R: Response> Execute for R {
+    fn execute<SPI, DELAY>(
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        cmd: &[u8],
+        busy_timeout: u32,
+        waits: &CommandWaits,
+    ) -> Result<Self, Error>
+    where
+        SPI: Write<u8> + Transfer<u8>,
+        DELAY: DelayTimer,
+    {
+        debug_assert_eq!(cmd.len(), 6);
+
+        wait_for_card(spi, delay, waits)?;
+
+        spi.write(cmd).map_err(|_| SpiWriteSnafu {}.build())?;
+
+        Self::receive_response(spi, delay, busy_timeout, waits)
+    }
+
+    fn receive_response<SPI, DELAY>(
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        busy_timeout: u32,
+        waits: &CommandWaits,
+    ) -> Result<Self, Error>
+    where
+        SPI: Transfer<u8>,
+        DELAY: DelayTimer,
+    {
+        let mut deadline = Deadline::new(delay, waits.response_timeout);
+
+        // The first byte of every response is an R1 response, whose most
+        // significant bit is always clear (see section 7.3.2.1). Any other
+        // byte is either the card not responding yet (0xff) or, after the
+        // StopTransmission command, data left over from the aborted read.
+        loop {
+            let recv = receive(spi)?;
+            if recv & 0b1000_0000 == 0 {
+                let r1 = R1Response::new(recv);
+                let mut extra = R::ExtraBytes::default();
+                if !r1.response_truncated() {
+                    for e in extra.as_mut().iter_mut() {
+                        *e = receive(spi)?;
+                    }
+                }
+
+                let response = r1
+                    .check_error()
+                    .context(CommandResponseSnafu {})
+                    .map(|r1| R::create(r1, &extra))?;
+
+                if R::BUSY {
+                    wait_while_busy(spi, delay, busy_timeout)?;
+                }
+
+                return Ok(response);
+            }
+
+            deadline
+                .check(delay, RESPONSE_BYTE_TIME)
+                .map_err(|elapsed_us| WaitForResponseTimeoutSnafu { elapsed_us }.build())?;
+        }
+    }
+}
+
+fn wait_for_card<SPI, DELAY>(
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    waits: &CommandWaits,
+) -> Result<(), Error>
+where
+    SPI: Transfer<u8>,
+    DELAY: DelayTimer,
+{
+    let mut deadline = Deadline::new(delay, waits.ready_timeout);
+
+    loop {
+        if receive(spi)? == 0xff {
+            return Ok(());
+        }
+
+        deadline
+            .delay(delay, waits.ready_delay)
+            .map_err(|elapsed_us| WaitForCardTimeoutSnafu { elapsed_us }.build())?;
+    }
+}
+
+/// A timeout that starts when the `Deadline` is created.
+///
+/// The elapsed time is measured with the timer of the delay if it has one.
+/// Otherwise it is estimated as the sum of the delays made through
+/// [`Deadline::delay`] and the times passed to [`Deadline::check`].
+struct Deadline {
+    start: Option<u32>,
+    timeout: u32,
+    delayed: u32,
+}
+
+impl Deadline {
+    fn new(delay: &mut impl DelayTimer, timeout: u32) -> Self {
+        Self {
+            start: delay.now_us(),
+            timeout,
+            delayed: 0,
+        }
+    }
+
+    /// Delay for `us` microseconds and then check whether the timeout has
+    /// passed.
+    ///
+    /// Once the timeout has passed this returns the elapsed time (in
+    /// microseconds) as the error.
+    fn delay(&mut self, delay: &mut impl DelayTimer, us: u16) -> Result<(), u32> {
+        delay.delay_us(us);
+        self.check(delay, us.into())
+    }
+
+    /// Check whether the timeout has passed without delaying, estimating
+    /// that `us` microseconds have passed since the last check if there is
+    /// no timer.
+    ///
+    /// Once the timeout has passed this returns the elapsed time (in
+    /// microseconds) as the error.
+    fn check(&mut self, delay: &mut impl DelayTimer, us: u32) -> Result<(), u32> {
+        self.delayed = self.delayed.saturating_add(us);
+
+        let elapsed = match (self.start, delay.now_us()) {
+            (Some(start), Some(now)) => now.wrapping_sub(start),
+            _ => self.delayed,
+        };
+
+        if elapsed < self.timeout {
+            Ok(())
+        } else {
+            Err(elapsed)
+        }
+    }
+}
+
+fn receive<SPI: Transfer<u8>>(spi: &mut SPI) -> Result<u8, Error> {
+    let mut buffer = [0xff];
+    let response = spi
+        .transfer(&mut buffer)
+        .map_err(|_| SpiTransferSnafu {}.build())?;
+
+    Ok(response[0])
+}
+
+/// How a range of bytes lines up with the data blocks on the card.
+///
+/// The range is split into a head in a partially covered first data block,
+/// a middle of fully covered data blocks, and a tail in a partially covered
+/// last data block. Any of these can be empty.
+struct BlockLayout {
+    /// The index of the first data block.
+    block: u32,
+
+    /// The number of data blocks that the range overlaps.
+    count: u32,
+
+    /// The offset of the start of the range within the first data block.
+    start: usize,
+
+    /// The length of the head of the range.
+    head_len: usize,
+
+    /// The length of the middle of the range.
+    middle_len: usize,
+}
+
+impl BlockLayout {
+    fn new(offset: u32, len: usize) -> Self {
+        let start = offset as usize % BLOCK_SIZE;
+        let head_len = if start == 0 {
+            0
+        } else {
+            cmp::min(BLOCK_SIZE - start, len)
+        };
+        let middle_len = (len - head_len) / BLOCK_SIZE * BLOCK_SIZE;
+        let tail_len = len - head_len - middle_len;
+
+        let count =
+            (middle_len / BLOCK_SIZE) as u32 + u32::from(head_len > 0) + u32::from(tail_len > 0);
+
+        Self {
+            block: offset / BLOCK_SIZE as u32,
+            count,
+            start,
+            head_len,
+            middle_len,
+        }
+    }
+}
+
+// This assumes that the SPI transfer is done in place (as is the case for
+// every implementation of Transfer that we are aware of) so that the bytes
+// received from the card end up in buffer.
+fn receive_into<SPI: Transfer<u8>>(spi: &mut SPI, buffer: &mut [u8]) -> Result<(), Error> {
+    buffer.iter_mut().for_each(|b| *b = 0xff);
+    spi.transfer(buffer)
+        .map_err(|_| SpiTransferSnafu {}.build())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io::ErrorKind, iter};
+
+    use crate::{
+        common,
+        testutils::{StepTimer, StubSpi, FAKE_CSD},
+        timer::TimedDelay,
+    };
+
+    use embedded_hal_mock::{delay, pin, spi, MockError};
+
+    use super::*;
+
+    #[test]
+    fn power_up_card_has_74_clocks_with_cs_high() {
+        let mut spi = spi::Mock::new(&[spi::Transaction::write([0xff; 10].to_vec())]);
+        let mut cs = pin::Mock::new(&[pin::Transaction::set(pin::State::High)]);
+        let mut delay = delay::MockNoop::new();
+
+        power_up_card(&mut spi, &mut cs, &mut delay).expect("Unable to power up");
+
+        spi.done();
+        cs.done();
+    }
+
+    #[test]
+    fn power_up_card_handles_cs_high_error() {
+        let go_high = pin::Transaction::set(pin::State::High)
+            .with_error(MockError::Io(ErrorKind::Unsupported));
+        let mut spi = spi::Mock::new(&[spi::Transaction::write([0xff; 10].to_vec())]);
+        let mut cs = pin::Mock::new(&[go_high]);
+        let mut delay = delay::MockNoop::new();
+
+        let result = power_up_card(&mut spi, &mut cs, &mut delay);
+
+        assert_eq!(result, Err(Error::ChipSelect));
+    }
+
+    #[test]
+    fn with_cs_low_toggles_cs() {
+        let set_low = pin::Transaction::set(pin::State::Low);
+        let set_high = pin::Transaction::set(pin::State::High);
+        let mut cs = pin::Mock::new(&[set_low, set_high]);
+        let mut delay = delay::MockNoop::new();
+
+        let _ = with_cs_low(&mut cs, &mut StubSpi, &mut delay, |_, _| Ok(()));
+
+        cs.done();
+    }
+
+    #[test]
+    fn wait_for_card_is_ok_after_cipo_high() {
+        let expected = [
+            spi::Transaction::transfer(vec![0xff], vec![0x00]),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]),
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+        ];
+        let mut spi = spi::Mock::new(&expected);
+        let mut delay = delay::MockNoop::new();
+
+        let result = wait_for_card(&mut spi, &mut delay, &CommandWaits::DEFAULT);
+
+        spi.done();
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn wait_for_card_is_error_after_too_much_cipo_low() {
+        let count = WAIT_FOR_CARD_TIMEOUT / u32::from(WAIT_FOR_CARD_DELAY);
+        let expectations: Vec<_> = (0..count)
+            .map(|_| spi::Transaction::transfer(vec![0xff], vec![0x00]))
+            .collect();
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        let result = wait_for_card(&mut spi, &mut delay, &CommandWaits::DEFAULT);
+
+        spi.done();
+        assert_eq!(
+            result,
+            Err(Error::WaitForCardTimeout {
+                elapsed_us: WAIT_FOR_CARD_TIMEOUT
+            })
+        );
+    }
+
+    #[test]
+    fn wait_for_card_with_timer_times_out_after_ready_timeout() {
+        let expectations: Vec<_> = (0..3)
+            .map(|_| spi::Transaction::transfer(vec![0xff], vec![0x00]))
+            .collect();
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = TimedDelay {
+            delay: delay::MockNoop::new(),
+            timer: Some(StepTimer::new(0, 1_200)),
+        };
+
+        let result = wait_for_card(&mut spi, &mut delay, &CommandWaits::DEFAULT);
+
+        spi.done();
+        assert_eq!(result, Err(Error::WaitForCardTimeout { elapsed_us: 3_600 }));
+    }
+
+    #[test]
+    fn execute_command_writes_command() {
+        let command = vec![0x01, 0x02, 0x03, 0x04, 0x05, 0x06];
+        let expectations = [
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(command.clone()),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]),
+        ];
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        execute_command(&mut spi, &mut delay, &command, &CommandWaits::DEFAULT)
+            .expect("error executing command");
+
+        spi.done();
+    }
+
+    #[test]
+    fn execute_command_with_error_response_is_error() {
+        let command = vec![0x01, 0x02, 0x03, 0x04, 0x05, 0x06];
+        let expectations = [
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(command.clone()),
+            spi::Transaction::transfer(vec![0xff], vec![0b0100_0000]),
+        ];
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        let result = execute_command(&mut spi, &mut delay, &command, &CommandWaits::DEFAULT);
+
+        spi.done();
+        assert!(matches!(result, Err(Error::CommandResponse { source: _ })));
+    }
+
+    #[test]
+    fn execute_command_with_no_response_times_out() {
+        let command = vec![0x01, 0x02, 0x03, 0x04, 0x05, 0x06];
+        let expectations = [
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(command.clone()),
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+        ];
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        let result = execute_command(&mut spi, &mut delay, &command, &CommandWaits::DEFAULT);
+
+        spi.done();
+        assert_eq!(
+            result,
+            Err(Error::WaitForResponseTimeout {
+                elapsed_us: RESPONSE_TIMEOUT
+            })
+        );
+    }
+
+    #[test]
+    fn execute_command_with_timer_times_out_after_response_timeout() {
+        let command = vec![0x01, 0x02, 0x03, 0x04, 0x05, 0x06];
+        let expectations = [
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(command.clone()),
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+        ];
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = TimedDelay {
+            delay: delay::MockNoop::new(),
+            timer: Some(StepTimer::new(0, 400)),
+        };
+
+        let result = execute_command(&mut spi, &mut delay, &command, &CommandWaits::DEFAULT);
+
+        spi.done();
+        assert_eq!(
+            result,
+            Err(Error::WaitForResponseTimeout { elapsed_us: 800 })
+        );
+    }
+
+    #[test]
+    fn r1b_execute_command_waits_while_busy() {
+        let command = vec![0x01, 0x02, 0x03, 0x04, 0x05, 0x06];
+        let expectations = [
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(command.clone()),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]), // R1 with no error
+            spi::Transaction::transfer(vec![0xff], vec![0x00]), // busy
+            spi::Transaction::transfer(vec![0xff], vec![0x00]), // busy
+            spi::Transaction::transfer(vec![0xff], vec![0xff]), // not busy
+        ];
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        R1bResponse::execute_command(&mut spi, &mut delay, &command, &CommandWaits::DEFAULT)
+            .expect("error executing command");
+
+        spi.done();
+    }
+
+    #[test]
+    fn r1b_execute_command_with_busy_timeout_is_error_after_timeout() {
+        let command = vec![0x01, 0x02, 0x03, 0x04, 0x05, 0x06];
+        let mut expectations = vec![
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(command.clone()),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]), // R1 with no error
+        ];
+        expectations.extend((0..3).map(|_| spi::Transaction::transfer(vec![0xff], vec![0x00])));
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        let result = R1bResponse::execute_command_with_busy_timeout(
+            &mut spi,
+            &mut delay,
+            &command,
+            3 * u32::from(WAIT_WHILE_BUSY_DELAY),
+            &CommandWaits::DEFAULT,
+        );
+
+        spi.done();
+        assert_eq!(result, Err(Error::BusyTimeout { elapsed_us: 300 }));
+    }
+
+    #[test]
+    fn r7_execute_command_does_not_recv_extra_bytes_for_truncated_r1() {
+        let command = vec![0b0100_1000, 0, 0, common::VOLTAGE_2_7_TO_3_6, 85, 117];
+        let expectations = [
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(command.clone()),
+            spi::Transaction::transfer(vec![0xff], vec![0b0000_0100]), // R1 with illegal command
+        ];
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        let _result =
+            R7Response::execute_command(&mut spi, &mut delay, &command, &CommandWaits::DEFAULT);
+
+        spi.done();
+    }
+
+    #[test]
+    fn r7_exectute_command_recv_extra_bytes_for_non_error_r1() {
+        let command = vec![0b0100_1000, 0, 0, common::VOLTAGE_2_7_TO_3_6, 85, 117];
+        let expectations = [
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(command.clone()),
+            spi::Transaction::transfer(vec![0xff], vec![0b0000_0000]),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]),
+        ];
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        let _result =
+            R7Response::execute_command(&mut spi, &mut delay, &command, &CommandWaits::DEFAULT);
+
+        spi.done();
+    }
+
+    #[test]
+    fn r7_execute_command_recv_extra_bytes_for_non_truncate_error_r1() {
+        let command = vec![0b0100_1000, 0, 0, common::VOLTAGE_2_7_TO_3_6, 85, 117];
+        let expectations = [
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(command.clone()),
+            spi::Transaction::transfer(vec![0xff], vec![0b0100_0000]), // R1 with parameter error
+            spi::Transaction::transfer(vec![0xff], vec![0x00]),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]),
+        ];
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        let _result =
+            R7Response::execute_command(&mut spi, &mut delay, &command, &CommandWaits::DEFAULT);
+
+        spi.done();
+    }
+
+    #[test]
+    fn send_if_cond_illegal_command_is_v1() {
+        let command = vec![0b0100_1000, 0, 0, common::VOLTAGE_2_7_TO_3_6, 85, 117];
+        let expectations = [
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(command),
+            spi::Transaction::transfer(vec![0xff], vec![0b0000_0100]), // R1 with illegal command
+        ];
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        let result = send_if_cond(&mut spi, &mut delay, &InitConfig::default());
+
+        spi.done();
+        assert!(matches!(result, Ok(Version::V1)));
+    }
+
+    #[test]
+    fn send_if_cond_with_valid_r7_is_v2() {
+        let command = vec![0b0100_1000, 0, 0, common::VOLTAGE_2_7_TO_3_6, 85, 117];
+        let expectations = [
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(command),
+            spi::Transaction::transfer(vec![0xff], vec![0]), // R1 (R7 byte 1)
+            spi::Transaction::transfer(vec![0xff], vec![0]), // R7 byte 2
+            spi::Transaction::transfer(vec![0xff], vec![0]), // R7 byte 3
+            spi::Transaction::transfer(vec![0xff], vec![common::VOLTAGE_2_7_TO_3_6]), // R7 byte 4
+            spi::Transaction::transfer(vec![0xff], vec![85]), // R7 byte 5
+        ];
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        let result = send_if_cond(&mut spi, &mut delay, &InitConfig::default());
+
+        spi.done();
+        assert!(matches!(result, Ok(Version::V2)));
+    }
+
+    #[test]
+    fn send_if_cond_with_valid_r7_on_second_try_is_v2() {
+        let command = vec![0b0100_1000, 0, 0, common::VOLTAGE_2_7_TO_3_6, 85, 117];
+        let expectations = [
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(command.clone()),
+            spi::Transaction::transfer(vec![0xff], vec![0]), // R1 (R7 byte 1)
+            spi::Transaction::transfer(vec![0xff], vec![0]), // R7 byte 2
+            spi::Transaction::transfer(vec![0xff], vec![0]), // R7 byte 3
+            spi::Transaction::transfer(vec![0xff], vec![common::VOLTAGE_2_7_TO_3_6]), // R7 byte 4
+            spi::Transaction::transfer(vec![0xff], vec![12]), // R7 byte 5
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(command),
+            spi::Transaction::transfer(vec![0xff], vec![0]), // R1 (R7 byte 1)
+            spi::Transaction::transfer(vec![0xff], vec![0]), // R7 byte 2
+            spi::Transaction::transfer(vec![0xff], vec![0]), // R7 byte 3
+            spi::Transaction::transfer(vec![0xff], vec![common::VOLTAGE_2_7_TO_3_6]), // R7 byte 4
+            spi::Transaction::transfer(vec![0xff], vec![85]), // R7 byte 5
+        ];
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        let result = send_if_cond(&mut spi, &mut delay, &InitConfig::default());
+
+        spi.done();
+        assert!(matches!(result, Ok(Version::V2)));
+    }
+
+    #[test]
+    fn send_if_cond_with_repeated_invalid_r7_is_unusable() {
+        let check_pattern = common::IF_COND_CHECK_PATTERN;
+        let not_check_pattern = check_pattern + 5;
+        let command = vec![
+            0b0100_1000,
+            0,
+            0,
+            common::VOLTAGE_2_7_TO_3_6,
+            check_pattern,
+            117,
+        ];
+        let mut expectations = Vec::new();
+        for _ in 0..MAX_IF_COND_COUNT {
+            expectations.extend([
+                spi::Transaction::transfer(vec![0xff], vec![0xff]),
+                spi::Transaction::write(command.clone()),
+                spi::Transaction::transfer(vec![0xff], vec![0]), // R1 (R7 byte 1)
+                spi::Transaction::transfer(vec![0xff], vec![0]), // R7 byte 2
+                spi::Transaction::transfer(vec![0xff], vec![0]), // R7 byte 3
+                spi::Transaction::transfer(vec![0xff], vec![common::VOLTAGE_2_7_TO_3_6]), // R7 byte 4
+                spi::Transaction::transfer(vec![0xff], vec![not_check_pattern]), // R7 byte 5
+            ]);
+        }
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        let result = send_if_cond(&mut spi, &mut delay, &InitConfig::default());
+
+        spi.done();
+        assert!(matches!(result, Err(Error::UnusableCard)));
+    }
+
+    #[test]
+    fn send_op_cond_for_v1_supports_sdsc_as_expected() {
+        let app_cmd = vec![0b0111_0111, 0, 0, 0, 0, 101];
+        let op_cond_cmd = vec![0b0110_1001, 0b0000_0000, 0, 0, 0, 229];
+        let expectations = [
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(app_cmd),
+            spi::Transaction::transfer(vec![0xff], vec![0]), // R1 with no error and not idle
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(op_cond_cmd),
+            spi::Transaction::transfer(vec![0xff], vec![0]), // R1 with no error and not idle
+        ];
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        let result = send_op_cond(&mut spi, Version::V1, &mut delay, &InitConfig::default());
+
+        spi.done();
+        assert_eq!(result, Ok(CardType::Sd));
+    }
+
+    #[test]
+    fn send_op_cond_for_v2_with_sc_only_host_clears_hcs() {
+        let app_cmd = vec![0b0111_0111, 0, 0, 0, 0, 101];
+        let op_cond_cmd = vec![0b0110_1001, 0b0000_0000, 0, 0, 0, 229];
+        let expectations = [
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(app_cmd),
+            spi::Transaction::transfer(vec![0xff], vec![0]), // R1 with no error and not idle
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(op_cond_cmd),
+            spi::Transaction::transfer(vec![0xff], vec![0]), // R1 with no error and not idle
+        ];
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+        let config = InitConfig {
+            host_capacity_support: HostCapacitySupport::ScOnly,
+            ..InitConfig::default()
+        };
+
+        let result = send_op_cond(&mut spi, Version::V2, &mut delay, &config);
+
+        spi.done();
+        assert_eq!(result, Ok(CardType::Sd));
+    }
+
+    #[test]
+    fn send_op_cond_with_configured_timeout_times_out_after_timeout() {
+        let app_cmd = vec![0b0111_0111, 0, 0, 0, 0, 101];
+        let op_cond_cmd = vec![0b0110_1001, 0b0100_0000, 0, 0, 0, 119];
+        let mut expectations = Vec::new();
+        for _ in 0..2 {
+            expectations.extend([
+                spi::Transaction::transfer(vec![0xff], vec![0xff]),
+                spi::Transaction::write(app_cmd.clone()),
+                spi::Transaction::transfer(vec![0xff], vec![0b0000_0001]), // R1 with no error and idle
+                spi::Transaction::transfer(vec![0xff], vec![0xff]),
+                spi::Transaction::write(op_cond_cmd.clone()),
+                spi::Transaction::transfer(vec![0xff], vec![0b0000_0001]), // R1 with no error and idle
+            ]);
+        }
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+        let config = InitConfig {
+            op_cond_timeout: 2 * u32::from(OP_COND_DELAY),
+            ..InitConfig::default()
+        };
+
+        let result = send_op_cond(&mut spi, Version::V2, &mut delay, &config);
+
+        spi.done();
+        assert_eq!(result, Err(Error::OpCondTimeout { elapsed_us: 100 }));
+    }
+
+    #[test]
+    fn send_op_cond_for_v2_supports_hc_and_xc_as_expected() {
+        let app_cmd = vec![0b0111_0111, 0, 0, 0, 0, 101];
+        let op_cond_cmd = vec![0b0110_1001, 0b0100_0000, 0, 0, 0, 119];
+        let expectations = [
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(app_cmd),
+            spi::Transaction::transfer(vec![0xff], vec![0]), // R1 with no error and not idle
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(op_cond_cmd),
+            spi::Transaction::transfer(vec![0xff], vec![0]), // R1 with no error and not idle
+        ];
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        send_op_cond(&mut spi, Version::V2, &mut delay, &InitConfig::default())
+            .expect("Unable to send op cond.");
+
+        spi.done();
+    }
+
+    #[test]
+    fn send_op_cond_with_illegal_app_cmd_falls_back_to_mmc() {
+        let app_cmd = vec![0b0111_0111, 0, 0, 0, 0, 101];
+        let mut mmc_op_cond_cmd = [0; 6];
+        cmds::send_op_cond(&mut mmc_op_cond_cmd);
+        let expectations = [
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(app_cmd),
+            spi::Transaction::transfer(vec![0xff], vec![0b0000_0101]), // R1 with illegal command and idle
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(mmc_op_cond_cmd.to_vec()),
+            spi::Transaction::transfer(vec![0xff], vec![0b0000_0001]), // R1 with no error and idle
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(mmc_op_cond_cmd.to_vec()),
+            spi::Transaction::transfer(vec![0xff], vec![0b0000_0000]), // R1 with no error and not idle
+        ];
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        let result = send_op_cond(&mut spi, Version::V1, &mut delay, &InitConfig::default());
+
+        spi.done();
+        assert_eq!(result, Ok(CardType::Mmc));
+    }
+
+    #[test]
+    fn send_op_cond_with_idle_response_repeats() {
+        let app_cmd = vec![0b0111_0111, 0, 0, 0, 0, 101];
+        let op_cond_cmd = vec![0b0110_1001, 0b0100_0000, 0, 0, 0, 119];
+        let expectations = [
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(app_cmd.clone()),
+            spi::Transaction::transfer(vec![0xff], vec![0b0000_0001]), // R1 with no error and idle
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(op_cond_cmd.clone()),
+            spi::Transaction::transfer(vec![0xff], vec![0b0000_0001]), // R1 with no error and idle
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(app_cmd),
+            spi::Transaction::transfer(vec![0xff], vec![0b0000_0001]), // R1 with no error and idle
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(op_cond_cmd),
+            spi::Transaction::transfer(vec![0xff], vec![0b0000_0000]), // R1 with no error and not idle
+        ];
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        send_op_cond(&mut spi, Version::V2, &mut delay, &InitConfig::default())
+            .expect("Unable to send op cond.");
+
+        spi.done();
+    }
+
+    #[test]
+    fn send_op_cond_with_repeated_idle_response_times_out() {
+        let app_cmd = vec![0b0111_0111, 0, 0, 0, 0, 101];
+        let op_cond_cmd = vec![0b0110_1001, 0b0100_0000, 0, 0, 0, 119];
+        let mut expectations = Vec::new();
+        for _ in 0..OP_COND_TIMEOUT / u32::from(OP_COND_DELAY) {
+            expectations.extend([
+                spi::Transaction::transfer(vec![0xff], vec![0xff]),
+                spi::Transaction::write(app_cmd.clone()),
+                spi::Transaction::transfer(vec![0xff], vec![0b0000_0001]), // R1 with no error and idle
+                spi::Transaction::transfer(vec![0xff], vec![0xff]),
+                spi::Transaction::write(op_cond_cmd.clone()),
+                spi::Transaction::transfer(vec![0xff], vec![0b0000_0001]), // R1 with no error and idle
+            ]);
+        }
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        let result = send_op_cond(&mut spi, Version::V2, &mut delay, &InitConfig::default());
+
+        spi.done();
+        assert_eq!(
+            result,
+            Err(Error::OpCondTimeout {
+                elapsed_us: OP_COND_TIMEOUT
+            })
+        );
+    }
+
+    #[test]
+    fn send_op_cond_with_timer_times_out_after_one_second() {
+        let app_cmd = vec![0b0111_0111, 0, 0, 0, 0, 101];
+        let op_cond_cmd = vec![0b0110_1001, 0b0100_0000, 0, 0, 0, 119];
+        let mut expectations = Vec::new();
+        // Each command reads the timer to start its ready and response waits,
+        // and the check after each attempt reads it once more.
+        for _ in 0..2 {
+            expectations.extend([
+                spi::Transaction::transfer(vec![0xff], vec![0xff]),
+                spi::Transaction::write(app_cmd.clone()),
+                spi::Transaction::transfer(vec![0xff], vec![0b0000_0001]), // R1 with no error and idle
+                spi::Transaction::transfer(vec![0xff], vec![0xff]),
+                spi::Transaction::write(op_cond_cmd.clone()),
+                spi::Transaction::transfer(vec![0xff], vec![0b0000_0001]), // R1 with no error and idle
+            ]);
+        }
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = TimedDelay {
+            delay: delay::MockNoop::new(),
+            timer: Some(StepTimer::new(u32::MAX - 100_000, 100_000)),
+        };
+
+        let result = send_op_cond(&mut spi, Version::V2, &mut delay, &InitConfig::default());
+
+        spi.done();
+        assert_eq!(
+            result,
+            Err(Error::OpCondTimeout {
+                elapsed_us: 1_000_000
+            })
+        );
+    }
+
+    #[test]
+    fn check_card_capacity_for_v1_is_standard() {
+        let mut spi = spi::Mock::new(iter::empty());
+        let mut delay = delay::MockNoop::new();
+
+        let result = check_card_capacity(
+            &mut spi,
+            &mut delay,
+            Version::V1,
+            CardType::Sd,
+            &InitConfig::default(),
+        );
+
+        spi.done();
+        assert_eq!(result, Ok(CardCapacity::Standard));
+    }
+
+    #[test]
+    fn check_card_capacity_for_v2_without_ccs_is_standard() {
+        let expectations = [
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(vec![0b0111_1010, 0, 0, 0, 0, 253]),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]),
+        ];
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        let result = check_card_capacity(
+            &mut spi,
+            &mut delay,
+            Version::V2,
+            CardType::Sd,
+            &InitConfig::default(),
+        );
+
+        spi.done();
+        assert_eq!(result, Ok(CardCapacity::Standard));
+    }
+
+    #[test]
+    fn check_card_capacity_for_v2_withccs_is_high_or_extended() {
+        let expectations = [
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(vec![0b0111_1010, 0, 0, 0, 0, 253]),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]),
+            spi::Transaction::transfer(vec![0xff], vec![0b0100_0000]),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]),
+        ];
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        let result = check_card_capacity(
+            &mut spi,
+            &mut delay,
+            Version::V2,
+            CardType::Sd,
+            &InitConfig::default(),
+        );
+
+        spi.done();
+        assert_eq!(result, Ok(CardCapacity::HighOrExtended));
+    }
+
+    #[test]
+    fn check_card_capacity_for_byte_mode_mmc_is_standard() {
+        let expectations = [
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(vec![0b0111_1010, 0, 0, 0, 0, 253]),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]),
+            spi::Transaction::transfer(vec![0xff], vec![0b1000_0000]),
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::transfer(vec![0xff], vec![0x80]),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]),
+        ];
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        let result = check_card_capacity(
+            &mut spi,
+            &mut delay,
+            Version::V1,
+            CardType::Mmc,
+            &InitConfig::default(),
+        );
+
+        spi.done();
+        assert_eq!(result, Ok(CardCapacity::Standard));
+    }
+
+    #[test]
+    fn check_card_capacity_for_sector_mode_mmc_is_unsupported() {
+        let expectations = [
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(vec![0b0111_1010, 0, 0, 0, 0, 253]),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]),
+            spi::Transaction::transfer(vec![0xff], vec![0b1100_0000]),
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::transfer(vec![0xff], vec![0x80]),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]),
+        ];
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        let result = check_card_capacity(
+            &mut spi,
+            &mut delay,
+            Version::V1,
+            CardType::Mmc,
+            &InitConfig::default(),
+        );
+
+        spi.done();
+        assert_eq!(
+            result,
+            Err(Error::UnsupportedCard {
+                card_type: UnsupportedCardType::SectorModeMmc
+            })
+        );
+    }
+
+    fn io_send_op_cond_expectations(r1: u8, r4: &[u8]) -> Vec<spi::Transaction> {
+        let mut command = [0; 6];
+        cmds::io_send_op_cond(&mut command);
+        let mut expectations = vec![
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(command.to_vec()),
+            spi::Transaction::transfer(vec![0xff], vec![r1]),
+        ];
+        expectations.extend(
+            r4.iter()
+                .map(|byte| spi::Transaction::transfer(vec![0xff], vec![*byte])),
+        );
+        expectations
+    }
+
+    #[test]
+    fn probe_io_for_memory_card_has_no_io_functions() {
+        // R1 with illegal command and idle
+        let mut spi = spi::Mock::new(&io_send_op_cond_expectations(0b0000_0101, &[]));
+        let mut delay = delay::MockNoop::new();
+
+        let result = probe_io(&mut spi, &mut delay, &InitConfig::default());
+
+        spi.done();
+        assert_eq!(result, Ok(0));
+    }
+
+    #[test]
+    fn probe_io_for_sdio_card_is_unsupported() {
+        let mut spi = spi::Mock::new(&io_send_op_cond_expectations(
+            0b0000_0001,
+            &[0b0001_0000, 0xff, 0x80, 0x00],
+        ));
+        let mut delay = delay::MockNoop::new();
+
+        let result = probe_io(&mut spi, &mut delay, &InitConfig::default());
+
+        spi.done();
+        assert_eq!(
+            result,
+            Err(Error::UnsupportedCard {
+                card_type: UnsupportedCardType::Sdio
+            })
+        );
+    }
+
+    #[test]
+    fn probe_io_for_combo_card_has_io_functions() {
+        let mut spi = spi::Mock::new(&io_send_op_cond_expectations(
+            0b0000_0001,
+            &[0b0001_1000, 0xff, 0x80, 0x00],
+        ));
+        let mut delay = delay::MockNoop::new();
+
+        let result = probe_io(&mut spi, &mut delay, &InitConfig::default());
+
+        spi.done();
+        assert_eq!(result, Ok(1));
+    }
+
+    fn read_ocr_expectations(ocr: [u8; 4]) -> Vec<spi::Transaction> {
+        let mut expectations = vec![
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(vec![0b0111_1010, 0, 0, 0, 0, 253]),
+            spi::Transaction::transfer(vec![0xff], vec![0x01]),
+        ];
+        expectations.extend(
+            ocr.iter()
+                .map(|byte| spi::Transaction::transfer(vec![0xff], vec![*byte])),
+        );
+        expectations
+    }
+
+    #[test]
+    fn check_voltage_inside_window_is_ok() {
+        let mut spi = spi::Mock::new(&read_ocr_expectations([0x00, 0xff, 0x80, 0x00]));
+        let mut delay = delay::MockNoop::new();
+
+        let result = check_voltage(&mut spi, &mut delay, &InitConfig::default());
+
+        spi.done();
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn check_voltage_outside_window_is_unsupported() {
+        // 2.7 V to 3.0 V (bits 15 to 17)
+        let mut spi = spi::Mock::new(&read_ocr_expectations([0x00, 0x03, 0x80, 0x00]));
         let mut delay = delay::MockNoop::new();
 
-        let _ = with_cs_low(&mut cs, &mut StubSpi, &mut delay, |_, _| Ok(()));
+        let result = check_voltage(&mut spi, &mut delay, &InitConfig::default());
 
-        cs.done();
+        spi.done();
+        assert!(matches!(
+            result,
+            Err(Error::UnsupportedVoltage {
+                host_voltage: 3_300,
+                ..
+            })
+        ));
     }
 
     #[test]
-    fn wait_for_card_is_ok_after_cipo_high() {
-        let expected = [
-            spi::Transaction::transfer(vec![0xff], vec![0x00]),
-            spi::Transaction::transfer(vec![0xff], vec![0x00]),
+    fn read_aligned_block_reads_into_bytes() {
+        let data: Vec<u8> = (0..BLOCK_SIZE).map(|i| i as u8).collect();
+        let mut spi = spi::Mock::new(&read_block_expectations(
+            read_single_block_command(BLOCK_SIZE as u32),
+            &data,
+        ));
+        let mut delay = delay::MockNoop::new();
+        let mut bytes = [0; BLOCK_SIZE];
+
+        read(
+            &mut spi,
+            &mut delay,
+            &CommandWaits::DEFAULT,
+            CardCapacity::Standard,
+            BLOCK_SIZE as u32,
+            &mut bytes,
+        )
+        .expect("Unable to read");
+
+        spi.done();
+        assert_eq!(bytes.as_ref(), data.as_slice());
+    }
+
+    #[test]
+    fn read_unaligned_range_copies_requested_bytes() {
+        let data1 = [0x11; BLOCK_SIZE];
+        let data2 = [0x22; BLOCK_SIZE];
+        let mut expectations = read_block_expectations(read_multiple_block_command(0), &data1);
+        expectations.extend(data_block_expectations(&data2));
+        expectations.extend(stop_transmission_expectations());
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+        let mut bytes = [0; 4];
+
+        read(
+            &mut spi,
+            &mut delay,
+            &CommandWaits::DEFAULT,
+            CardCapacity::HighOrExtended,
+            BLOCK_SIZE as u32 - 2,
+            &mut bytes,
+        )
+        .expect("Unable to read");
+
+        spi.done();
+        assert_eq!(bytes, [0x11, 0x11, 0x22, 0x22]);
+    }
+
+    #[test]
+    fn read_several_blocks_uses_read_multiple_block() {
+        let data1 = [0x11; BLOCK_SIZE];
+        let data2 = [0x22; BLOCK_SIZE];
+        let data3 = [0x33; BLOCK_SIZE];
+        let mut expectations =
+            read_block_expectations(read_multiple_block_command(4 * BLOCK_SIZE as u32), &data1);
+        expectations.extend(data_block_expectations(&data2));
+        expectations.extend(data_block_expectations(&data3));
+        expectations.extend(stop_transmission_expectations());
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+        let mut bytes = [0; 3 * BLOCK_SIZE];
+
+        read(
+            &mut spi,
+            &mut delay,
+            &CommandWaits::DEFAULT,
+            CardCapacity::Standard,
+            4 * BLOCK_SIZE as u32,
+            &mut bytes,
+        )
+        .expect("Unable to read");
+
+        spi.done();
+        assert_eq!(bytes[..BLOCK_SIZE], data1);
+        assert_eq!(bytes[BLOCK_SIZE..2 * BLOCK_SIZE], data2);
+        assert_eq!(bytes[2 * BLOCK_SIZE..], data3);
+    }
+
+    #[test]
+    fn read_several_blocks_with_crc_mismatch_stops_transmission() {
+        let data = [0x11; BLOCK_SIZE];
+        let mut expectations = read_block_expectations(read_multiple_block_command(0), &data);
+        expectations.pop();
+        expectations.push(spi::Transaction::transfer(vec![0xff; 2], vec![0, 0]));
+        expectations.extend(stop_transmission_expectations());
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+        let mut bytes = [0; 2 * BLOCK_SIZE];
+
+        let result = read(
+            &mut spi,
+            &mut delay,
+            &CommandWaits::DEFAULT,
+            CardCapacity::HighOrExtended,
+            0,
+            &mut bytes,
+        );
+
+        spi.done();
+        assert_eq!(result, Err(Error::DataCrcMismatch));
+    }
+
+    #[test]
+    fn read_with_crc_mismatch_is_error() {
+        let data = [0x42; BLOCK_SIZE];
+        let mut expectations = read_block_expectations(read_single_block_command(0), &data);
+        expectations.pop();
+        expectations.push(spi::Transaction::transfer(vec![0xff; 2], vec![0, 0]));
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+        let mut bytes = [0; BLOCK_SIZE];
+
+        let result = read(
+            &mut spi,
+            &mut delay,
+            &CommandWaits::DEFAULT,
+            CardCapacity::HighOrExtended,
+            0,
+            &mut bytes,
+        );
+
+        spi.done();
+        assert_eq!(result, Err(Error::DataCrcMismatch));
+    }
+
+    #[test]
+    fn read_with_data_error_token_is_error() {
+        let expectations = [
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(read_single_block_command(0).to_vec()),
             spi::Transaction::transfer(vec![0xff], vec![0x00]),
             spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::transfer(vec![0xff], vec![0b0000_1000]), // out of range
         ];
-        let mut spi = spi::Mock::new(&expected);
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+        let mut bytes = [0; BLOCK_SIZE];
+
+        let result = read(
+            &mut spi,
+            &mut delay,
+            &CommandWaits::DEFAULT,
+            CardCapacity::HighOrExtended,
+            0,
+            &mut bytes,
+        );
+
+        spi.done();
+        assert_eq!(
+            result,
+            Err(Error::DataToken {
+                source: TokenError::OutOfRange
+            })
+        );
+    }
+
+    #[test]
+    fn write_aligned_block_writes_bytes_and_waits_while_busy() {
+        let data: Vec<u8> = (0..BLOCK_SIZE).map(|i| i as u8).collect();
+        let mut spi = spi::Mock::new(&write_block_expectations(
+            write_block_command(BLOCK_SIZE as u32),
+            &data,
+            0b0000_0101, // data accepted
+        ));
+        let mut delay = delay::MockNoop::new();
+
+        write(
+            &mut spi,
+            &mut delay,
+            &CommandWaits::DEFAULT,
+            CardCapacity::Standard,
+            CardType::Sd,
+            BLOCK_SIZE as u32,
+            &data,
+        )
+        .expect("Unable to write");
+
+        spi.done();
+    }
+
+    #[test]
+    fn write_unaligned_range_preserves_other_bytes() {
+        let original = [0x11; BLOCK_SIZE];
+        let mut modified = original;
+        modified[10..14].copy_from_slice(&[1, 2, 3, 4]);
+        let mut expectations = read_block_expectations(read_single_block_command(3), &original);
+        expectations.extend(write_block_expectations(
+            write_block_command(3),
+            &modified,
+            0b0000_0101, // data accepted
+        ));
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        write(
+            &mut spi,
+            &mut delay,
+            &CommandWaits::DEFAULT,
+            CardCapacity::HighOrExtended,
+            CardType::Sd,
+            3 * BLOCK_SIZE as u32 + 10,
+            &[1, 2, 3, 4],
+        )
+        .expect("Unable to write");
+
+        spi.done();
+    }
+
+    #[test]
+    fn write_with_rejected_data_is_error() {
+        let data = [0x42; BLOCK_SIZE];
+        let mut expectations = write_block_expectations(
+            write_block_command(0),
+            &data,
+            0b0000_1011, // data rejected due to CRC error
+        );
+        expectations.truncate(expectations.len() - 2);
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        let result = write(
+            &mut spi,
+            &mut delay,
+            &CommandWaits::DEFAULT,
+            CardCapacity::HighOrExtended,
+            CardType::Sd,
+            0,
+            &data,
+        );
+
+        spi.done();
+        assert_eq!(
+            result,
+            Err(Error::DataResponse {
+                source: TokenError::DataCrcError
+            })
+        );
+    }
+
+    #[test]
+    fn write_several_blocks_to_mmc_skips_pre_erase() {
+        let data = [0x33; 2 * BLOCK_SIZE];
+        let mut expectations = command_expectations(write_multiple_block_command(0));
+        for block in data.chunks(BLOCK_SIZE) {
+            expectations.extend(write_data_expectations(
+                tokens::START_MULTIPLE_BLOCK_WRITE,
+                block,
+                0b0000_0101, // data accepted
+            ));
+        }
+        expectations.extend(stop_tran_expectations());
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        write(
+            &mut spi,
+            &mut delay,
+            &CommandWaits::DEFAULT,
+            CardCapacity::Standard,
+            CardType::Mmc,
+            0,
+            &data,
+        )
+        .expect("Unable to write");
+
+        spi.done();
+    }
+
+    #[test]
+    fn write_several_blocks_uses_write_multiple_block() {
+        let original1 = [0x11; BLOCK_SIZE];
+        let original2 = [0x22; BLOCK_SIZE];
+        let mut modified1 = original1;
+        let mut modified2 = original2;
+        modified1[BLOCK_SIZE - 2..].copy_from_slice(&[1, 2]);
+        modified2[..2].copy_from_slice(&[3, 4]);
+        let mut expectations = read_block_expectations(read_single_block_command(0), &original1);
+        expectations.extend(read_block_expectations(
+            read_single_block_command(1),
+            &original2,
+        ));
+        expectations.extend(pre_erase_expectations(2));
+        expectations.extend(command_expectations(write_multiple_block_command(0)));
+        expectations.extend(write_data_expectations(
+            tokens::START_MULTIPLE_BLOCK_WRITE,
+            &modified1,
+            0b0000_0101, // data accepted
+        ));
+        expectations.extend(write_data_expectations(
+            tokens::START_MULTIPLE_BLOCK_WRITE,
+            &modified2,
+            0b0000_0101, // data accepted
+        ));
+        expectations.extend(stop_tran_expectations());
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        write(
+            &mut spi,
+            &mut delay,
+            &CommandWaits::DEFAULT,
+            CardCapacity::HighOrExtended,
+            CardType::Sd,
+            BLOCK_SIZE as u32 - 2,
+            &[1, 2, 3, 4],
+        )
+        .expect("Unable to write");
+
+        spi.done();
+    }
+
+    #[test]
+    fn write_several_blocks_with_rejected_data_reports_blocks_written() {
+        let data = [0x42; 3 * BLOCK_SIZE];
+        let mut expectations = pre_erase_expectations(3);
+        expectations.extend(command_expectations(write_multiple_block_command(
+            BLOCK_SIZE as u32,
+        )));
+        expectations.extend(write_data_expectations(
+            tokens::START_MULTIPLE_BLOCK_WRITE,
+            &data[..BLOCK_SIZE],
+            0b0000_0101, // data accepted
+        ));
+        let mut rejected = write_data_expectations(
+            tokens::START_MULTIPLE_BLOCK_WRITE,
+            &data[BLOCK_SIZE..2 * BLOCK_SIZE],
+            0b0000_1101, // data rejected due to write error
+        );
+        rejected.truncate(rejected.len() - 2);
+        expectations.extend(rejected);
+        expectations.extend(stop_tran_expectations());
+        let mut command = [0; 6];
+        cmds::app_cmd(&mut command);
+        expectations.extend(command_expectations(command));
+        cmds::send_num_wr_blocks(&mut command);
+        expectations.extend(read_block_expectations(command, &[0, 0, 0, 1]));
+        cmds::send_status(&mut command);
+        expectations.extend(command_expectations(command));
+        expectations.push(spi::Transaction::transfer(vec![0xff], vec![0x00])); // R2 byte 2
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        let result = write(
+            &mut spi,
+            &mut delay,
+            &CommandWaits::DEFAULT,
+            CardCapacity::Standard,
+            CardType::Sd,
+            BLOCK_SIZE as u32,
+            &data,
+        );
+
+        spi.done();
+        assert_eq!(
+            result,
+            Err(Error::PartialWrite {
+                blocks_written: 1,
+                source: TokenError::WriteError
+            })
+        );
+    }
+
+    #[test]
+    fn wait_while_busy_is_error_after_too_much_busy() {
+        let expectations: Vec<_> = (0..5)
+            .map(|_| spi::Transaction::transfer(vec![0xff], vec![0x00]))
+            .collect();
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        let result = wait_while_busy(&mut spi, &mut delay, 5 * u32::from(WAIT_WHILE_BUSY_DELAY));
+
+        spi.done();
+        assert_eq!(result, Err(Error::BusyTimeout { elapsed_us: 500 }));
+    }
+
+    #[test]
+    fn wait_for_start_block_is_error_after_read_timeout() {
+        let expectations: Vec<_> = (0..READ_TIMEOUT / u32::from(WAIT_FOR_DATA_DELAY))
+            .map(|_| spi::Transaction::transfer(vec![0xff], vec![0xff]))
+            .collect();
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        let result = wait_for_start_block(&mut spi, &mut delay);
+
+        spi.done();
+        assert_eq!(
+            result,
+            Err(Error::WaitForDataTimeout {
+                elapsed_us: READ_TIMEOUT
+            })
+        );
+    }
+
+    #[test]
+    fn wait_for_start_block_with_timer_is_error_after_read_timeout() {
+        let expectations: Vec<_> = (0..4)
+            .map(|_| spi::Transaction::transfer(vec![0xff], vec![0xff]))
+            .collect();
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = TimedDelay {
+            delay: delay::MockNoop::new(),
+            timer: Some(StepTimer::new(0, 30_000)),
+        };
+
+        let result = wait_for_start_block(&mut spi, &mut delay);
+
+        spi.done();
+        assert_eq!(
+            result,
+            Err(Error::WaitForDataTimeout {
+                elapsed_us: 120_000
+            })
+        );
+    }
+
+    #[test]
+    fn write_block_to_sdsc_card_with_timer_is_error_after_write_timeout() {
+        let mut command = [0; 6];
+        cmds::write_block(0, &mut command);
+        let data = [0x5a; BLOCK_SIZE];
+        let mut expectations = vec![
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(command.to_vec()),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]), // R1 with no error
+            spi::Transaction::write(vec![0xff, tokens::START_BLOCK]),
+            spi::Transaction::write(data.to_vec()),
+            spi::Transaction::write(tokens::crc16(&data).to_be_bytes().to_vec()),
+            spi::Transaction::transfer(vec![0xff], vec![0b0000_0101]), // data accepted
+        ];
+        expectations.extend((0..3).map(|_| spi::Transaction::transfer(vec![0xff], vec![0x00])));
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = TimedDelay {
+            delay: delay::MockNoop::new(),
+            timer: Some(StepTimer::new(0, 100_000)),
+        };
+
+        let result = write_block(
+            &mut spi,
+            &mut delay,
+            &CommandWaits::DEFAULT,
+            CardCapacity::Standard,
+            0,
+            &data,
+        );
+
+        spi.done();
+        assert_eq!(
+            result,
+            Err(Error::BusyTimeout {
+                elapsed_us: 300_000
+            })
+        );
+    }
+
+    #[test]
+    fn read_csd_reads_data_block() {
+        let csd = [
+            0x40, 0x0e, 0x00, 0x32, 0x5b, 0x59, 0x00, 0x00, 0x3b, 0x37, 0x7f, 0x80, 0x0a, 0x40,
+            0x00, 0x01,
+        ];
+        let mut command = [0; 6];
+        cmds::send_csd(&mut command);
+        let mut spi = spi::Mock::new(&read_block_expectations(command, &csd));
+        let mut delay = delay::MockNoop::new();
+
+        let result = read_csd(&mut spi, &mut delay, &CommandWaits::DEFAULT, CardType::Sd)
+            .expect("Unable to read CSD");
+
+        spi.done();
+        assert_eq!(result, Csd::new(csd).unwrap());
+    }
+
+    #[test]
+    fn read_blocks_past_4_gib_uses_block_address() {
+        let data = [0x42; BLOCK_SIZE];
+        let block = 0x0080_0001;
+        let mut spi = spi::Mock::new(&read_block_expectations(
+            read_single_block_command(block),
+            &data,
+        ));
+        let mut delay = delay::MockNoop::new();
+        let mut buffer = [0; BLOCK_SIZE];
+
+        read_blocks(
+            &mut spi,
+            &mut delay,
+            &CommandWaits::DEFAULT,
+            CardCapacity::HighOrExtended,
+            block,
+            1,
+            iter::once(&mut buffer[..]),
+        )
+        .expect("Unable to read");
+
+        spi.done();
+        assert_eq!(buffer, data);
+    }
+
+    #[test]
+    fn read_blocks_past_4_gib_on_sdsc_card_is_error_without_command() {
+        let mut spi = spi::Mock::new(iter::empty());
+        let mut delay = delay::MockNoop::new();
+        let mut buffer = [0; BLOCK_SIZE];
+
+        let result = read_blocks(
+            &mut spi,
+            &mut delay,
+            &CommandWaits::DEFAULT,
+            CardCapacity::Standard,
+            0x0080_0000,
+            1,
+            iter::once(&mut buffer[..]),
+        );
+
+        spi.done();
+        assert_eq!(result, Err(Error::BlockOutOfRange { block: 0x0080_0000 }));
+    }
+
+    #[test]
+    fn write_blocks_past_4_gib_on_sdsc_card_is_error_without_command() {
+        let mut spi = spi::Mock::new(iter::empty());
+        let mut delay = delay::MockNoop::new();
+        let data = [0; BLOCK_SIZE];
+
+        let result = write_blocks(
+            &mut spi,
+            &mut delay,
+            &CommandWaits::DEFAULT,
+            CardCapacity::Standard,
+            CardType::Sd,
+            0x0080_0000,
+            2,
+            vec![&data[..]; 2].into_iter(),
+        );
+
+        spi.done();
+        assert_eq!(result, Err(Error::BlockOutOfRange { block: 0x0080_0000 }));
+    }
+
+    #[test]
+    fn block_layout_for_aligned_range_has_only_middle() {
+        let layout = BlockLayout::new(2 * BLOCK_SIZE as u32, 3 * BLOCK_SIZE);
+
+        assert_eq!(layout.block, 2);
+        assert_eq!(layout.count, 3);
+        assert_eq!(layout.head_len, 0);
+        assert_eq!(layout.middle_len, 3 * BLOCK_SIZE);
+    }
+
+    #[test]
+    fn block_layout_for_unaligned_range_has_head_and_tail() {
+        let layout = BlockLayout::new(BLOCK_SIZE as u32 + 12, 2 * BLOCK_SIZE);
+
+        assert_eq!(layout.block, 1);
+        assert_eq!(layout.count, 3);
+        assert_eq!(layout.start, 12);
+        assert_eq!(layout.head_len, BLOCK_SIZE - 12);
+        assert_eq!(layout.middle_len, BLOCK_SIZE);
+    }
+
+    #[test]
+    fn block_layout_for_range_within_block_has_only_head() {
+        let layout = BlockLayout::new(12, 20);
+
+        assert_eq!(layout.block, 0);
+        assert_eq!(layout.count, 1);
+        assert_eq!(layout.head_len, 20);
+        assert_eq!(layout.middle_len, 0);
+    }
+
+    #[test]
+    fn read_cid_reads_data_block() {
+        let cid = [
+            0x03, 0x53, 0x44, 0x53, 0x43, 0x31, 0x36, 0x47, 0x80, 0x12, 0x34, 0x56, 0x78, 0x01,
+            0x48, 0x9d,
+        ];
+        let mut command = [0; 6];
+        cmds::send_cid(&mut command);
+        let mut spi = spi::Mock::new(&read_block_expectations(command, &cid));
+        let mut delay = delay::MockNoop::new();
+
+        let result =
+            read_cid(&mut spi, &mut delay, &CommandWaits::DEFAULT).expect("Unable to read CID");
+
+        spi.done();
+        assert_eq!(result, Cid::new(cid).unwrap());
+    }
+
+    #[test]
+    fn read_scr_reads_data_block_after_app_cmd() {
+        let scr = [0x02, 0xb5, 0x80, 0x03, 0, 0, 0, 0];
+        let mut command = [0; 6];
+        cmds::app_cmd(&mut command);
+        let mut expectations = command_expectations(command);
+        cmds::send_scr(&mut command);
+        expectations.extend(read_block_expectations(command, &scr));
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        let result =
+            read_scr(&mut spi, &mut delay, &CommandWaits::DEFAULT).expect("Unable to read SCR");
+
+        spi.done();
+        assert_eq!(result, Scr::new(scr).unwrap());
+    }
+
+    #[test]
+    fn read_sd_status_reads_data_block_after_r2() {
+        let mut status = [0; 64];
+        status[8] = 0x04;
+        let mut command = [0; 6];
+        cmds::app_cmd(&mut command);
+        let mut expectations = command_expectations(command);
+        cmds::sd_status(&mut command);
+        expectations.extend(command_expectations(command));
+        expectations.push(spi::Transaction::transfer(vec![0xff], vec![0x00])); // R2 byte 2
+        expectations.extend(data_block_expectations(&status));
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        let result = read_sd_status(&mut spi, &mut delay, &CommandWaits::DEFAULT)
+            .expect("Unable to read SD Status");
+
+        spi.done();
+        assert_eq!(result, SdStatus::new(status));
+    }
+
+    #[test]
+    fn read_sd_status_with_r2_error_is_error() {
+        let mut command = [0; 6];
+        cmds::app_cmd(&mut command);
+        let mut expectations = command_expectations(command);
+        cmds::sd_status(&mut command);
+        expectations.extend(command_expectations(command));
+        expectations.push(spi::Transaction::transfer(vec![0xff], vec![0b0000_0100])); // R2 byte 2
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        let result = read_sd_status(&mut spi, &mut delay, &CommandWaits::DEFAULT);
+
+        spi.done();
+        assert_eq!(
+            result,
+            Err(Error::CardStatus {
+                source: CardStatusError::CardError
+            })
+        );
+    }
+
+    #[test]
+    fn erase_uses_byte_addresses_for_standard_capacity() {
+        let mut command = [0; 6];
+        cmds::erase_wr_blk_start_addr(2 * BLOCK_SIZE as u32, &mut command);
+        let mut expectations = command_expectations(command);
+        cmds::erase_wr_blk_end_addr(5 * BLOCK_SIZE as u32, &mut command);
+        expectations.extend(command_expectations(command));
+        cmds::erase(EraseKind::Erase, &mut command);
+        expectations.extend(command_expectations(command));
+        expectations.push(spi::Transaction::transfer(vec![0xff], vec![0x00])); // busy
+        expectations.push(spi::Transaction::transfer(vec![0xff], vec![0xff])); // not busy
+        let mut spi = spi::Mock::new(&expectations);
+        let mut delay = delay::MockNoop::new();
+
+        let result = erase(
+            &mut spi,
+            &mut delay,
+            &CommandWaits::DEFAULT,
+            CardCapacity::Standard,
+            CardType::Sd,
+            2,
+            5,
+            EraseKind::Erase,
+            DEFAULT_BUSY_TIMEOUT,
+        );
+
+        spi.done();
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn erase_uses_erase_group_commands_for_mmc() {
+        let mut command = [0; 6];
+        cmds::erase_group_start(0, &mut command);
+        let mut expectations = command_expectations(command);
+        cmds::erase_group_end(31 * BLOCK_SIZE as u32, &mut command);
+        expectations.extend(command_expectations(command));
+        cmds::erase(EraseKind::Erase, &mut command);
+        expectations.extend(command_expectations(command));
+        expectations.push(spi::Transaction::transfer(vec![0xff], vec![0xff])); // not busy
+        let mut spi = spi::Mock::new(&expectations);
         let mut delay = delay::MockNoop::new();
 
-        let result = wait_for_card(&mut spi, &mut delay);
+        let result = erase(
+            &mut spi,
+            &mut delay,
+            &CommandWaits::DEFAULT,
+            CardCapacity::Standard,
+            CardType::Mmc,
+            0,
+            31,
+            EraseKind::Erase,
+            DEFAULT_BUSY_TIMEOUT,
+        );
 
         spi.done();
         assert_eq!(result, Ok(()));
     }
 
     #[test]
-    fn wait_for_card_is_error_after_too_much_cipo_low() {
-        let mut spi = spi::Mock::new(
-            iter::repeat(&spi::Transaction::transfer(vec![0xff], vec![0x00]))
-                .take(WAIT_FOR_CARD_COUNT.try_into().unwrap()),
-        );
+    fn erase_with_end_before_start_is_error() {
+        let mut spi = spi::Mock::new(&[]);
         let mut delay = delay::MockNoop::new();
 
-        let result = wait_for_card(&mut spi, &mut delay);
+        let result = erase(
+            &mut spi,
+            &mut delay,
+            &CommandWaits::DEFAULT,
+            CardCapacity::HighOrExtended,
+            CardType::Sd,
+            5,
+            2,
+            EraseKind::Erase,
+            DEFAULT_BUSY_TIMEOUT,
+        );
 
-        assert_eq!(result, Err(Error::WaitForCardTimeout));
+        spi.done();
+        assert_eq!(result, Err(Error::InvalidEraseRange));
     }
 
     #[test]
-    fn execute_command_writes_command() {
-        let command = vec![0x01, 0x02, 0x03, 0x04, 0x05, 0x06];
-        let expectations = [
-            spi::Transaction::transfer(vec![0xff], vec![0xff]),
-            spi::Transaction::write(command.clone()),
-            spi::Transaction::transfer(vec![0xff], vec![0x00]),
-        ];
+    fn program_csd_writes_csd_data_block() {
+        let csd = Csd::new(FAKE_CSD).unwrap();
+        let mut command = [0; 6];
+        cmds::program_csd(&mut command);
+        let mut expectations = write_block_expectations(command, csd.as_bytes(), 0b0000_0101);
+        cmds::send_status(&mut command);
+        expectations.extend(command_expectations(command));
+        expectations.push(spi::Transaction::transfer(vec![0xff], vec![0x00])); // R2 byte 2
         let mut spi = spi::Mock::new(&expectations);
         let mut delay = delay::MockNoop::new();
 
-        execute_command(&mut spi, &mut delay, &command).expect("error executing command");
+        let result = program_csd(&mut spi, &mut delay, &CommandWaits::DEFAULT, &csd);
 
         spi.done();
+        assert_eq!(result, Ok(()));
     }
 
     #[test]
-    fn execute_command_with_error_response_is_error() {
-        let command = vec![0x01, 0x02, 0x03, 0x04, 0x05, 0x06];
-        let expectations = [
-            spi::Transaction::transfer(vec![0xff], vec![0xff]),
-            spi::Transaction::write(command.clone()),
-            spi::Transaction::transfer(vec![0xff], vec![0b0100_0000]),
-        ];
+    fn program_csd_with_csd_overwrite_status_is_error() {
+        let csd = Csd::new(FAKE_CSD).unwrap();
+        let mut command = [0; 6];
+        cmds::program_csd(&mut command);
+        let mut expectations = write_block_expectations(command, csd.as_bytes(), 0b0000_0101);
+        cmds::send_status(&mut command);
+        expectations.extend(command_expectations(command));
+        expectations.push(spi::Transaction::transfer(vec![0xff], vec![0b1000_0000])); // R2 byte 2
         let mut spi = spi::Mock::new(&expectations);
         let mut delay = delay::MockNoop::new();
 
-        let result = execute_command(&mut spi, &mut delay, &command);
+        let result = program_csd(&mut spi, &mut delay, &CommandWaits::DEFAULT, &csd);
 
         spi.done();
-        assert!(matches!(result, Err(Error::CommandResponse { source: _ })));
+        assert_eq!(
+            result,
+            Err(Error::CardStatus {
+                source: CardStatusError::OutOfRange
+            })
+        );
     }
 
     #[test]
-    fn execute_command_with_no_response_times_out() {
-        let command = vec![0x01, 0x02, 0x03, 0x04, 0x05, 0x06];
-        let expectations = [
-            spi::Transaction::transfer(vec![0xff], vec![0xff]),
-            spi::Transaction::write(command.clone()),
-            spi::Transaction::transfer(vec![0xff], vec![0xff]),
-            spi::Transaction::transfer(vec![0xff], vec![0xff]),
-            spi::Transaction::transfer(vec![0xff], vec![0xff]),
-            spi::Transaction::transfer(vec![0xff], vec![0xff]),
-            spi::Transaction::transfer(vec![0xff], vec![0xff]),
-            spi::Transaction::transfer(vec![0xff], vec![0xff]),
-            spi::Transaction::transfer(vec![0xff], vec![0xff]),
-            spi::Transaction::transfer(vec![0xff], vec![0xff]),
-        ];
+    fn lock_unlock_sets_block_length_around_data_block() {
+        let mut command = [0; 6];
+        cmds::set_block_len(2 + 4, &mut command);
+        let mut expectations = command_expectations(command);
+        cmds::lock_unlock(&mut command);
+        expectations.extend(write_block_expectations(
+            command,
+            &[0b0000_0100, 4, b'p', b'a', b's', b's'],
+            0b0000_0101, // data accepted
+        ));
+        cmds::set_block_len(BLOCK_SIZE as u32, &mut command);
+        expectations.extend(command_expectations(command));
+        cmds::send_status(&mut command);
+        expectations.extend(command_expectations(command));
+        expectations.push(spi::Transaction::transfer(vec![0xff], vec![0b0000_0001])); // locked
         let mut spi = spi::Mock::new(&expectations);
         let mut delay = delay::MockNoop::new();
 
-        let result = execute_command(&mut spi, &mut delay, &command);
+        let result = lock_unlock(
+            &mut spi,
+            &mut delay,
+            &CommandWaits::DEFAULT,
+            LockUnlockOp::Lock(b"pass"),
+        );
 
         spi.done();
-        assert!(matches!(result, Err(Error::WaitForResponseTimeout)));
+        assert_eq!(result, Ok(true));
     }
 
     #[test]
-    fn r7_execute_command_does_not_recv_extra_bytes_for_truncated_r1() {
-        let command = vec![0b0100_1000, 0, 0, common::VOLTAGE_2_7_TO_3_6, 85, 117];
-        let expectations = [
-            spi::Transaction::transfer(vec![0xff], vec![0xff]),
-            spi::Transaction::write(command.clone()),
-            spi::Transaction::transfer(vec![0xff], vec![0b0000_0100]), // R1 with illegal command
-        ];
+    fn lock_unlock_with_failed_status_is_error() {
+        let mut command = [0; 6];
+        cmds::set_block_len(2 + 4, &mut command);
+        let mut expectations = command_expectations(command);
+        cmds::lock_unlock(&mut command);
+        expectations.extend(write_block_expectations(
+            command,
+            &[0b0000_0000, 4, b'p', b'a', b's', b's'],
+            0b0000_0101, // data accepted
+        ));
+        cmds::set_block_len(BLOCK_SIZE as u32, &mut command);
+        expectations.extend(command_expectations(command));
+        cmds::send_status(&mut command);
+        expectations.extend(command_expectations(command));
+        expectations.push(spi::Transaction::transfer(vec![0xff], vec![0b0000_0011])); // failed
         let mut spi = spi::Mock::new(&expectations);
         let mut delay = delay::MockNoop::new();
 
-        let _result = R7Response::execute_command(&mut spi, &mut delay, &command);
+        let result = lock_unlock(
+            &mut spi,
+            &mut delay,
+            &CommandWaits::DEFAULT,
+            LockUnlockOp::Unlock(b"pass"),
+        );
 
         spi.done();
+        assert_eq!(
+            result,
+            Err(Error::CardStatus {
+                source: CardStatusError::WpEraseSkipOrLockUnlockFailed
+            })
+        );
     }
 
     #[test]
-    fn r7_exectute_command_recv_extra_bytes_for_non_error_r1() {
-        let command = vec![0b0100_1000, 0, 0, common::VOLTAGE_2_7_TO_3_6, 85, 117];
-        let expectations = [
-            spi::Transaction::transfer(vec![0xff], vec![0xff]),
-            spi::Transaction::write(command.clone()),
-            spi::Transaction::transfer(vec![0xff], vec![0b0000_0000]),
-            spi::Transaction::transfer(vec![0xff], vec![0x00]),
-            spi::Transaction::transfer(vec![0xff], vec![0x00]),
-            spi::Transaction::transfer(vec![0xff], vec![0x00]),
-            spi::Transaction::transfer(vec![0xff], vec![0x00]),
-        ];
-        let mut spi = spi::Mock::new(&expectations);
+    fn lock_unlock_with_long_password_is_error() {
+        let mut spi = spi::Mock::new(&[]);
         let mut delay = delay::MockNoop::new();
 
-        let _result = R7Response::execute_command(&mut spi, &mut delay, &command);
+        let result = lock_unlock(
+            &mut spi,
+            &mut delay,
+            &CommandWaits::DEFAULT,
+            LockUnlockOp::Lock(&[0x42; 17]),
+        );
 
         spi.done();
+        assert_eq!(result, Err(Error::InvalidPassword));
     }
 
     #[test]
-    fn r7_execute_command_recv_extra_bytes_for_non_truncate_error_r1() {
-        let command = vec![0b0100_1000, 0, 0, common::VOLTAGE_2_7_TO_3_6, 85, 117];
-        let expectations = [
-            spi::Transaction::transfer(vec![0xff], vec![0xff]),
-            spi::Transaction::write(command.clone()),
-            spi::Transaction::transfer(vec![0xff], vec![0b0100_0000]), // R1 with parameter error
-            spi::Transaction::transfer(vec![0xff], vec![0x00]),
-            spi::Transaction::transfer(vec![0xff], vec![0x00]),
-            spi::Transaction::transfer(vec![0xff], vec![0x00]),
-            spi::Transaction::transfer(vec![0xff], vec![0x00]),
-        ];
+    fn set_write_prot_waits_while_busy() {
+        let mut command = [0; 6];
+        cmds::set_write_prot(3 * BLOCK_SIZE as u32, &mut command);
+        let mut expectations = command_expectations(command);
+        expectations.push(spi::Transaction::transfer(vec![0xff], vec![0x00])); // busy
+        expectations.push(spi::Transaction::transfer(vec![0xff], vec![0xff])); // not busy
         let mut spi = spi::Mock::new(&expectations);
         let mut delay = delay::MockNoop::new();
 
-        let _result = R7Response::execute_command(&mut spi, &mut delay, &command);
+        let result = set_write_prot(
+            &mut spi,
+            &mut delay,
+            &CommandWaits::DEFAULT,
+            CardCapacity::Standard,
+            3,
+        );
 
         spi.done();
+        assert_eq!(result, Ok(()));
     }
 
     #[test]
-    fn send_if_cond_illegal_command_is_v1() {
-        let command = vec![0b0100_1000, 0, 0, common::VOLTAGE_2_7_TO_3_6, 85, 117];
-        let expectations = [
-            spi::Transaction::transfer(vec![0xff], vec![0xff]),
-            spi::Transaction::write(command),
-            spi::Transaction::transfer(vec![0xff], vec![0b0000_0100]), // R1 with illegal command
-        ];
+    fn send_write_prot_reads_protection_bits() {
+        let mut command = [0; 6];
+        cmds::send_write_prot(3 * BLOCK_SIZE as u32, &mut command);
+        let expectations = read_block_expectations(command, &[0x80, 0x00, 0x00, 0x05]);
         let mut spi = spi::Mock::new(&expectations);
         let mut delay = delay::MockNoop::new();
 
-        let result = send_if_cond(&mut spi, &mut delay);
+        let result = send_write_prot(
+            &mut spi,
+            &mut delay,
+            &CommandWaits::DEFAULT,
+            CardCapacity::Standard,
+            3,
+        );
 
         spi.done();
-        assert!(matches!(result, Ok(Version::V1)));
+        assert_eq!(result, Ok(0x8000_0005));
     }
 
     #[test]
-    fn send_if_cond_with_valid_r7_is_v2() {
-        let command = vec![0b0100_1000, 0, 0, common::VOLTAGE_2_7_TO_3_6, 85, 117];
-        let expectations = [
-            spi::Transaction::transfer(vec![0xff], vec![0xff]),
-            spi::Transaction::write(command),
-            spi::Transaction::transfer(vec![0xff], vec![0]), // R1 (R7 byte 1)
-            spi::Transaction::transfer(vec![0xff], vec![0]), // R7 byte 2
-            spi::Transaction::transfer(vec![0xff], vec![0]), // R7 byte 3
-            spi::Transaction::transfer(vec![0xff], vec![common::VOLTAGE_2_7_TO_3_6]), // R7 byte 4
-            spi::Transaction::transfer(vec![0xff], vec![85]), // R7 byte 5
-        ];
+    fn write_to_write_protected_block_is_wp_violation() {
+        let data = [0x42; BLOCK_SIZE];
+        let mut expectations = write_block_expectations(
+            write_block_command(0),
+            &data,
+            0b0000_1101, // data rejected due to write error
+        );
+        expectations.truncate(expectations.len() - 2);
+        let mut command = [0; 6];
+        cmds::send_status(&mut command);
+        expectations.extend(command_expectations(command));
+        expectations.push(spi::Transaction::transfer(vec![0xff], vec![0b0010_0000])); // R2 byte 2
         let mut spi = spi::Mock::new(&expectations);
         let mut delay = delay::MockNoop::new();
 
-        let result = send_if_cond(&mut spi, &mut delay);
+        let result = write(
+            &mut spi,
+            &mut delay,
+            &CommandWaits::DEFAULT,
+            CardCapacity::Standard,
+            CardType::Sd,
+            0,
+            &data,
+        );
 
         spi.done();
-        assert!(matches!(result, Ok(Version::V2)));
+        assert_eq!(
+            result,
+            Err(Error::CardStatus {
+                source: CardStatusError::WpViolation
+            })
+        );
     }
 
     #[test]
-    fn send_if_cond_with_valid_r7_on_second_try_is_v2() {
-        let command = vec![0b0100_1000, 0, 0, common::VOLTAGE_2_7_TO_3_6, 85, 117];
-        let expectations = [
-            spi::Transaction::transfer(vec![0xff], vec![0xff]),
-            spi::Transaction::write(command.clone()),
-            spi::Transaction::transfer(vec![0xff], vec![0]), // R1 (R7 byte 1)
-            spi::Transaction::transfer(vec![0xff], vec![0]), // R7 byte 2
-            spi::Transaction::transfer(vec![0xff], vec![0]), // R7 byte 3
-            spi::Transaction::transfer(vec![0xff], vec![common::VOLTAGE_2_7_TO_3_6]), // R7 byte 4
-            spi::Transaction::transfer(vec![0xff], vec![12]), // R7 byte 5
-            spi::Transaction::transfer(vec![0xff], vec![0xff]),
-            spi::Transaction::write(command),
-            spi::Transaction::transfer(vec![0xff], vec![0]), // R1 (R7 byte 1)
-            spi::Transaction::transfer(vec![0xff], vec![0]), // R7 byte 2
-            spi::Transaction::transfer(vec![0xff], vec![0]), // R7 byte 3
-            spi::Transaction::transfer(vec![0xff], vec![common::VOLTAGE_2_7_TO_3_6]), // R7 byte 4
-            spi::Transaction::transfer(vec![0xff], vec![85]), // R7 byte 5
-        ];
+    fn switch_to_high_speed_switches_when_supported() {
+        let mut status = [0; 64];
+        status[13] = 0b0000_0011; // group 1 supports default and high speed
+        status[16] = 0x01; // group 1 selects high speed
+        let mut command = [0; 6];
+        cmds::switch_func(SwitchFuncMode::Check, 1, &mut command);
+        let mut expectations = read_block_expectations(command, &status);
+        cmds::switch_func(SwitchFuncMode::Switch, 1, &mut command);
+        expectations.extend(read_block_expectations(command, &status));
         let mut spi = spi::Mock::new(&expectations);
         let mut delay = delay::MockNoop::new();
 
-        let result = send_if_cond(&mut spi, &mut delay);
+        let result = switch_to_high_speed(&mut spi, &mut delay, &CommandWaits::DEFAULT);
 
         spi.done();
-        assert!(matches!(result, Ok(Version::V2)));
+        assert_eq!(result, Ok(true));
     }
 
     #[test]
-    fn send_if_cond_with_repeated_invalid_r7_is_unusable() {
-        let check_pattern = common::IF_COND_CHECK_PATTERN;
-        let not_check_pattern = check_pattern + 5;
-        let command = vec![
-            0b0100_1000,
-            0,
-            0,
-            common::VOLTAGE_2_7_TO_3_6,
-            check_pattern,
-            117,
-        ];
-        let mut expectations = Vec::new();
-        for _ in 0..MAX_IF_COND_COUNT {
-            expectations.extend([
-                spi::Transaction::transfer(vec![0xff], vec![0xff]),
-                spi::Transaction::write(command.clone()),
-                spi::Transaction::transfer(vec![0xff], vec![0]), // R1 (R7 byte 1)
-                spi::Transaction::transfer(vec![0xff], vec![0]), // R7 byte 2
-                spi::Transaction::transfer(vec![0xff], vec![0]), // R7 byte 3
-                spi::Transaction::transfer(vec![0xff], vec![common::VOLTAGE_2_7_TO_3_6]), // R7 byte 4
-                spi::Transaction::transfer(vec![0xff], vec![not_check_pattern]), // R7 byte 5
-            ]);
-        }
+    fn switch_to_high_speed_does_not_switch_when_unsupported() {
+        let mut status = [0; 64];
+        status[13] = 0b0000_0001; // group 1 supports only default speed
+        status[16] = 0x0f; // group 1 can't select high speed
+        let mut command = [0; 6];
+        cmds::switch_func(SwitchFuncMode::Check, 1, &mut command);
+        let expectations = read_block_expectations(command, &status);
         let mut spi = spi::Mock::new(&expectations);
         let mut delay = delay::MockNoop::new();
 
-        let result = send_if_cond(&mut spi, &mut delay);
+        let result = switch_to_high_speed(&mut spi, &mut delay, &CommandWaits::DEFAULT);
 
         spi.done();
-        assert!(matches!(result, Err(Error::UnusableCard)));
+        assert_eq!(result, Ok(false));
     }
 
     #[test]
-    fn send_op_cond_for_v1_supports_sdsc_as_expected() {
-        let app_cmd = vec![0b0111_0111, 0, 0, 0, 0, 101];
-        let op_cond_cmd = vec![0b0110_1001, 0b0000_0000, 0, 0, 0, 229];
-        let expectations = [
-            spi::Transaction::transfer(vec![0xff], vec![0xff]),
-            spi::Transaction::write(app_cmd),
-            spi::Transaction::transfer(vec![0xff], vec![0]), // R1 with no error and not idle
-            spi::Transaction::transfer(vec![0xff], vec![0xff]),
-            spi::Transaction::write(op_cond_cmd),
-            spi::Transaction::transfer(vec![0xff], vec![0]), // R1 with no error and not idle
-        ];
+    fn send_status_with_clear_status_is_ok() {
+        let mut command = [0; 6];
+        cmds::send_status(&mut command);
+        let mut expectations = command_expectations(command);
+        expectations.push(spi::Transaction::transfer(vec![0xff], vec![0x00])); // R2 byte 2
         let mut spi = spi::Mock::new(&expectations);
         let mut delay = delay::MockNoop::new();
 
-        send_op_cond(&mut spi, Version::V1, &mut delay).expect("Unable to send op cond.");
+        let result = send_status(&mut spi, &mut delay, &CommandWaits::DEFAULT);
 
         spi.done();
+        assert_eq!(result, Ok(()));
     }
 
     #[test]
-    fn send_op_cond_for_v2_supports_hc_and_xc_as_expected() {
-        let app_cmd = vec![0b0111_0111, 0, 0, 0, 0, 101];
-        let op_cond_cmd = vec![0b0110_1001, 0b0100_0000, 0, 0, 0, 119];
-        let expectations = [
-            spi::Transaction::transfer(vec![0xff], vec![0xff]),
-            spi::Transaction::write(app_cmd),
-            spi::Transaction::transfer(vec![0xff], vec![0]), // R1 with no error and not idle
-            spi::Transaction::transfer(vec![0xff], vec![0xff]),
-            spi::Transaction::write(op_cond_cmd),
-            spi::Transaction::transfer(vec![0xff], vec![0]), // R1 with no error and not idle
-        ];
+    fn send_status_uses_given_ready_waits() {
+        let expectations: Vec<_> = (0..2)
+            .map(|_| spi::Transaction::transfer(vec![0xff], vec![0x00]))
+            .collect();
         let mut spi = spi::Mock::new(&expectations);
         let mut delay = delay::MockNoop::new();
+        let waits = CommandWaits {
+            ready_timeout: 2 * u32::from(WAIT_FOR_CARD_DELAY),
+            ..CommandWaits::DEFAULT
+        };
 
-        send_op_cond(&mut spi, Version::V2, &mut delay).expect("Unable to send op cond.");
+        let result = send_status(&mut spi, &mut delay, &waits);
 
         spi.done();
+        assert_eq!(
+            result,
+            Err(Error::WaitForCardTimeout {
+                elapsed_us: 2 * u32::from(WAIT_FOR_CARD_DELAY)
+            })
+        );
     }
 
     #[test]
-    fn send_op_cond_with_idle_response_repeats() {
-        let app_cmd = vec![0b0111_0111, 0, 0, 0, 0, 101];
-        let op_cond_cmd = vec![0b0110_1001, 0b0100_0000, 0, 0, 0, 119];
-        let expectations = [
-            spi::Transaction::transfer(vec![0xff], vec![0xff]),
-            spi::Transaction::write(app_cmd.clone()),
-            spi::Transaction::transfer(vec![0xff], vec![0b0000_0001]), // R1 with no error and idle
-            spi::Transaction::transfer(vec![0xff], vec![0xff]),
-            spi::Transaction::write(op_cond_cmd.clone()),
-            spi::Transaction::transfer(vec![0xff], vec![0b0000_0001]), // R1 with no error and idle
-            spi::Transaction::transfer(vec![0xff], vec![0xff]),
-            spi::Transaction::write(app_cmd),
-            spi::Transaction::transfer(vec![0xff], vec![0b0000_0001]), // R1 with no error and idle
-            spi::Transaction::transfer(vec![0xff], vec![0xff]),
-            spi::Transaction::write(op_cond_cmd),
-            spi::Transaction::transfer(vec![0xff], vec![0b0000_0000]), // R1 with no error and not idle
-        ];
-        let mut spi = spi::Mock::new(&expectations);
+    fn stop_transmission_uses_given_response_waits() {
+        let mut command = [0; 6];
+        cmds::stop_transmission(&mut command);
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(command.to_vec()),
+            spi::Transaction::transfer(vec![0xff], vec![0x42]), // stuff byte
+            spi::Transaction::transfer(vec![0xff], vec![0xff]), // no response yet
+        ]);
         let mut delay = delay::MockNoop::new();
+        let waits = CommandWaits {
+            response_timeout: RESPONSE_BYTE_TIME,
+            ..CommandWaits::DEFAULT
+        };
 
-        send_op_cond(&mut spi, Version::V2, &mut delay).expect("Unable to send op cond.");
+        let result = stop_transmission(&mut spi, &mut delay, &waits);
 
         spi.done();
+        assert_eq!(
+            result,
+            Err(Error::WaitForResponseTimeout {
+                elapsed_us: RESPONSE_BYTE_TIME
+            })
+        );
     }
 
     #[test]
-    fn send_op_cond_with_repeated_idle_response_is_unuable() {
-        let app_cmd = vec![0b0111_0111, 0, 0, 0, 0, 101];
-        let op_cond_cmd = vec![0b0110_1001, 0b0100_0000, 0, 0, 0, 119];
-        let mut expectations = Vec::new();
-        for _ in 0..MAX_OP_COND_COUNT {
-            expectations.extend([
-                spi::Transaction::transfer(vec![0xff], vec![0xff]),
-                spi::Transaction::write(app_cmd.clone()),
-                spi::Transaction::transfer(vec![0xff], vec![0b0000_0001]), // R1 with no error and idle
-                spi::Transaction::transfer(vec![0xff], vec![0xff]),
-                spi::Transaction::write(op_cond_cmd.clone()),
-                spi::Transaction::transfer(vec![0xff], vec![0b0000_0001]), // R1 with no error and idle
-            ]);
-        }
-        let mut spi = spi::Mock::new(&expectations);
+    fn stop_transmission_skips_leftover_data_before_r1() {
+        let mut command = [0; 6];
+        cmds::stop_transmission(&mut command);
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(command.to_vec()),
+            spi::Transaction::transfer(vec![0xff], vec![0x42]), // stuff byte
+            spi::Transaction::transfer(vec![0xff], vec![0xa5]), // leftover data
+            spi::Transaction::transfer(vec![0xff], vec![0x00]), // R1 with no error
+            spi::Transaction::transfer(vec![0xff], vec![0xff]), // not busy
+        ]);
         let mut delay = delay::MockNoop::new();
 
-        let result = send_op_cond(&mut spi, Version::V2, &mut delay);
+        let result = stop_transmission(&mut spi, &mut delay, &CommandWaits::DEFAULT);
 
         spi.done();
-        assert_eq!(result, Err(Error::UnusableCard));
+        assert_eq!(result, Ok(()));
     }
 
     #[test]
-    fn check_card_capacity_for_v1_is_standard() {
-        let mut spi = spi::Mock::new(iter::empty());
+    fn send_status_with_ecc_failure_is_error() {
+        let mut command = [0; 6];
+        cmds::send_status(&mut command);
+        let mut expectations = command_expectations(command);
+        expectations.push(spi::Transaction::transfer(vec![0xff], vec![0b0001_0000])); // R2 byte 2
+        let mut spi = spi::Mock::new(&expectations);
         let mut delay = delay::MockNoop::new();
 
-        let result = check_card_capacity(&mut spi, &mut delay, Version::V1);
+        let result = send_status(&mut spi, &mut delay, &CommandWaits::DEFAULT);
 
         spi.done();
-        assert_eq!(result, Ok(CardCapacity::Standard));
+        assert_eq!(
+            result,
+            Err(Error::CardStatus {
+                source: CardStatusError::CardEccFailed
+            })
+        );
     }
 
-    #[test]
-    fn check_card_capacity_for_v2_without_ccs_is_standard() {
-        let expectations = [
+    fn write_block_command(address: u32) -> [u8; 6] {
+        let mut command = [0; 6];
+        cmds::write_block(address, &mut command);
+        command
+    }
+
+    fn write_block_expectations(
+        command: [u8; 6],
+        data: &[u8],
+        data_response: u8,
+    ) -> Vec<spi::Transaction> {
+        let mut expectations = command_expectations(command);
+        expectations.extend(write_data_expectations(
+            tokens::START_BLOCK,
+            data,
+            data_response,
+        ));
+        expectations
+    }
+
+    fn command_expectations(command: [u8; 6]) -> Vec<spi::Transaction> {
+        vec![
             spi::Transaction::transfer(vec![0xff], vec![0xff]),
-            spi::Transaction::write(vec![0b0111_1010, 0, 0, 0, 0, 253]),
-            spi::Transaction::transfer(vec![0xff], vec![0x00]),
-            spi::Transaction::transfer(vec![0xff], vec![0x00]),
-            spi::Transaction::transfer(vec![0xff], vec![0x00]),
-            spi::Transaction::transfer(vec![0xff], vec![0x00]),
-            spi::Transaction::transfer(vec![0xff], vec![0x00]),
-        ];
-        let mut spi = spi::Mock::new(&expectations);
-        let mut delay = delay::MockNoop::new();
+            spi::Transaction::write(command.to_vec()),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]), // R1 with no error
+        ]
+    }
 
-        let result = check_card_capacity(&mut spi, &mut delay, Version::V2);
+    fn write_data_expectations(token: u8, data: &[u8], data_response: u8) -> Vec<spi::Transaction> {
+        let crc = tokens::crc16(data).to_be_bytes();
+
+        vec![
+            spi::Transaction::write(vec![0xff, token]),
+            spi::Transaction::write(data.to_vec()),
+            spi::Transaction::write(crc.to_vec()),
+            spi::Transaction::transfer(vec![0xff], vec![data_response]),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]), // busy
+            spi::Transaction::transfer(vec![0xff], vec![0xff]), // not busy
+        ]
+    }
 
-        spi.done();
-        assert_eq!(result, Ok(CardCapacity::Standard));
+    fn write_multiple_block_command(address: u32) -> [u8; 6] {
+        let mut command = [0; 6];
+        cmds::write_multiple_block(address, &mut command);
+        command
     }
 
-    #[test]
-    fn check_card_capacity_for_v2_withccs_is_high_or_extended() {
-        let expectations = [
+    fn pre_erase_expectations(count: u32) -> Vec<spi::Transaction> {
+        let mut command = [0; 6];
+        cmds::app_cmd(&mut command);
+        let mut expectations = command_expectations(command);
+        cmds::set_wr_blk_erase_count(count, &mut command);
+        expectations.extend(command_expectations(command));
+        expectations
+    }
+
+    fn stop_tran_expectations() -> Vec<spi::Transaction> {
+        vec![
+            spi::Transaction::write(vec![tokens::STOP_TRAN]),
             spi::Transaction::transfer(vec![0xff], vec![0xff]),
-            spi::Transaction::write(vec![0b0111_1010, 0, 0, 0, 0, 253]),
-            spi::Transaction::transfer(vec![0xff], vec![0x00]),
-            spi::Transaction::transfer(vec![0xff], vec![0b0100_0000]),
-            spi::Transaction::transfer(vec![0xff], vec![0x00]),
-            spi::Transaction::transfer(vec![0xff], vec![0x00]),
-            spi::Transaction::transfer(vec![0xff], vec![0x00]),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]), // busy
+            spi::Transaction::transfer(vec![0xff], vec![0xff]), // not busy
+        ]
+    }
+
+    fn read_single_block_command(address: u32) -> [u8; 6] {
+        let mut command = [0; 6];
+        cmds::read_single_block(address, &mut command);
+        command
+    }
+
+    fn read_multiple_block_command(address: u32) -> [u8; 6] {
+        let mut command = [0; 6];
+        cmds::read_multiple_block(address, &mut command);
+        command
+    }
+
+    fn read_block_expectations(command: [u8; 6], data: &[u8]) -> Vec<spi::Transaction> {
+        let mut expectations = vec![
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::write(command.to_vec()),
+            spi::Transaction::transfer(vec![0xff], vec![0x00]), // R1 with no error
         ];
-        let mut spi = spi::Mock::new(&expectations);
-        let mut delay = delay::MockNoop::new();
+        expectations.extend(data_block_expectations(data));
+        expectations
+    }
+
+    fn stop_transmission_expectations() -> Vec<spi::Transaction> {
+        let mut command = [0; 6];
+        cmds::stop_transmission(&mut command);
+
+        vec![
+            spi::Transaction::write(command.to_vec()),
+            spi::Transaction::transfer(vec![0xff], vec![0x42]), // stuff byte
+            spi::Transaction::transfer(vec![0xff], vec![0x00]), // R1 with no error
+            spi::Transaction::transfer(vec![0xff], vec![0x00]), // busy
+            spi::Transaction::transfer(vec![0xff], vec![0xff]), // not busy
+        ]
+    }
 
-        let result = check_card_capacity(&mut spi, &mut delay, Version::V2);
+    fn data_block_expectations(data: &[u8]) -> Vec<spi::Transaction> {
+        let crc = tokens::crc16(data).to_be_bytes();
 
-        spi.done();
-        assert_eq!(result, Ok(CardCapacity::HighOrExtended));
+        vec![
+            spi::Transaction::transfer(vec![0xff], vec![0xff]),
+            spi::Transaction::transfer(vec![0xff], vec![tokens::START_BLOCK]),
+            spi::Transaction::transfer(vec![0xff; data.len()], data.to_vec()),
+            spi::Transaction::transfer(vec![0xff; 2], crc.to_vec()),
+        ]
     }
 }